@@ -2,20 +2,37 @@ use colored::Colorize;
 use porter_core::models::AgentSession;
 use serde_json::json;
 
-pub async fn start(server: &str, prompt: &str) -> anyhow::Result<()> {
+pub async fn start(
+    server: &str,
+    prompt: &str,
+    model: Option<&str>,
+    profile: Option<&str>,
+) -> anyhow::Result<()> {
     let client = reqwest::Client::new();
 
     let resp = client
         .post(format!("{server}/api/agents"))
-        .json(&json!({ "prompt": prompt }))
+        .json(&json!({ "prompt": prompt, "model": model, "profile": profile }))
         .send()
         .await?;
 
     if resp.status().is_success() {
         let session: AgentSession = resp.json().await?;
-        println!("{} Started agent session", "✓".green());
+        match session.queue_position {
+            Some(position) => println!(
+                "{} Queued agent session (position {position})",
+                "…".yellow()
+            ),
+            None => println!("{} Started agent session", "✓".green()),
+        }
         println!("  ID: {}", session.id.dimmed());
         println!("  Model: {}", session.model);
+        if let Some(ref profile) = session.profile {
+            println!("  Profile: {profile}");
+        }
+        if let Some(ref backend) = session.backend {
+            println!("  Backend: {backend}");
+        }
         println!("  Prompt: {}", session.prompt);
     } else {
         let status = resp.status();
@@ -47,20 +64,16 @@ pub async fn list(server: &str, status: Option<&str>) -> anyhow::Result<()> {
         println!("{}", "Agent Sessions:".bold());
         for session in &sessions {
             let status_icon = match session.status {
+                porter_core::models::AgentStatus::Queued => "…".yellow(),
                 porter_core::models::AgentStatus::Running => "▶".green(),
                 porter_core::models::AgentStatus::Paused => "⏸".yellow(),
                 porter_core::models::AgentStatus::Completed => "✓".green(),
                 porter_core::models::AgentStatus::Failed => "✕".red(),
             };
-            let prompt_preview = if session.prompt.len() > 60 {
-                format!("{}...", &session.prompt[..57])
-            } else {
-                session.prompt.clone()
-            };
             println!(
                 "  {status_icon} {} {} ({})",
                 session.id[..8].dimmed(),
-                prompt_preview,
+                prompt_preview(&session.prompt),
                 session.model.dimmed()
             );
         }
@@ -70,3 +83,122 @@ pub async fn list(server: &str, status: Option<&str>) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+pub async fn queue(server: &str) -> anyhow::Result<()> {
+    let client = reqwest::Client::new();
+
+    let resp = client.get(format!("{server}/api/agents/queue")).send().await?;
+
+    if resp.status().is_success() {
+        let sessions: Vec<AgentSession> = resp.json().await?;
+        print_queue(&sessions);
+    } else {
+        anyhow::bail!("Failed to list queue: {}", resp.status());
+    }
+
+    Ok(())
+}
+
+pub async fn move_in_queue(server: &str, id: &str, position: usize) -> anyhow::Result<()> {
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .put(format!("{server}/api/agents/{id}/queue"))
+        .json(&json!({ "position": position }))
+        .send()
+        .await?;
+
+    if resp.status().is_success() {
+        let sessions: Vec<AgentSession> = resp.json().await?;
+        println!("{} Moved session to position {position}", "✓".green());
+        print_queue(&sessions);
+    } else if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        anyhow::bail!("Session {id} is not queued");
+    } else {
+        anyhow::bail!("Failed to reorder queue: {}", resp.status());
+    }
+
+    Ok(())
+}
+
+pub async fn cancel(server: &str, id: &str) -> anyhow::Result<()> {
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("{server}/api/agents/{id}/cancel"))
+        .send()
+        .await?;
+
+    if resp.status().is_success() {
+        println!("{} Cancelled session {}", "✓".green(), id.dimmed());
+    } else if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        anyhow::bail!("Session {id} is not running or queued");
+    } else {
+        anyhow::bail!("Failed to cancel session: {}", resp.status());
+    }
+
+    Ok(())
+}
+
+pub async fn prune(server: &str, dry_run: bool) -> anyhow::Result<()> {
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(format!("{server}/api/agents/prune"))
+        .json(&json!({ "dry_run": dry_run }))
+        .send()
+        .await?;
+
+    if resp.status().is_success() {
+        let sessions: Vec<AgentSession> = resp.json().await?;
+        if sessions.is_empty() {
+            println!("{}", "No sessions to prune.".dimmed());
+            return Ok(());
+        }
+
+        if dry_run {
+            println!("{}", "Would delete:".bold());
+        } else {
+            println!("{} Deleted {} session(s):", "✓".green(), sessions.len());
+        }
+        for session in &sessions {
+            let finished = session.completed_at.unwrap_or(session.started_at);
+            println!(
+                "  {} {} ({})",
+                session.id[..8].dimmed(),
+                prompt_preview(&session.prompt),
+                finished.format("%Y-%m-%d").to_string().dimmed()
+            );
+        }
+    } else {
+        anyhow::bail!("Failed to prune sessions: {}", resp.status());
+    }
+
+    Ok(())
+}
+
+fn print_queue(sessions: &[AgentSession]) {
+    if sessions.is_empty() {
+        println!("{}", "Queue is empty.".dimmed());
+        return;
+    }
+
+    println!("{}", "Queued Sessions:".bold());
+    for (i, session) in sessions.iter().enumerate() {
+        println!(
+            "  {:>2}. {} {}",
+            i + 1,
+            session.id.dimmed(),
+            prompt_preview(&session.prompt)
+        );
+    }
+}
+
+/// `prompt`, shortened to 60 characters.
+fn prompt_preview(prompt: &str) -> String {
+    if prompt.chars().count() > 60 {
+        format!("{}...", prompt.chars().take(57).collect::<String>())
+    } else {
+        prompt.to_string()
+    }
+}
@@ -0,0 +1,60 @@
+use colored::Colorize;
+use porter_core::config::PorterConfig;
+use porter_core::db;
+use std::path::Path;
+
+/// Apply pending migrations, or with `status_only` just report them.
+pub async fn migrate(config_path: &str, status_only: bool) -> anyhow::Result<()> {
+    let path = Path::new(config_path);
+    if !path.exists() {
+        anyhow::bail!("Config file not found: {}", config_path);
+    }
+
+    let config = PorterConfig::load(path)?;
+    let pool = if status_only {
+        if !Path::new(&config.instance.db_path).exists() {
+            anyhow::bail!("Database not found: {}", config.instance.db_path);
+        }
+        db::open_pool_read_only(&config.instance.db_path).await?
+    } else {
+        let pool = db::open_pool(&config.instance.db_path).await?;
+        db::run_migrations(&pool).await?;
+        pool
+    };
+
+    let status = db::migration_status(&pool).await?;
+    println!("{}", "Schema Migrations".bold());
+    println!("  Database: {}", config.instance.db_path.cyan());
+    println!(
+        "  Version:  {} (binary supports {})",
+        status.current_version, status.latest_version
+    );
+
+    for migration in &status.migrations {
+        match migration.applied_at {
+            Some(applied_at) => println!(
+                "  {} {:>3} {} {}",
+                "●".green(),
+                migration.version,
+                migration.description,
+                applied_at.format("%Y-%m-%d %H:%M").to_string().dimmed()
+            ),
+            None => println!(
+                "  {} {:>3} {} {}",
+                "○".yellow(),
+                migration.version,
+                migration.description,
+                "pending".yellow()
+            ),
+        }
+    }
+
+    if status.current_version > status.latest_version {
+        println!(
+            "\n  {} Database is newer than this binary — upgrade porter",
+            "✕".red()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,413 @@
+//! `porter mcp` — a Model Context Protocol server over stdio that exposes
+//! Porter's own data (tasks, notifications, integrations) to agent sessions.
+//!
+//! Messages are newline-delimited JSON-RPC 2.0. stdout carries the protocol,
+//! so all logging goes to stderr.
+//!
+//! When the Porter server starts a session that routes permission prompts
+//! here, it sets `PORTER_URL` and `PORTER_SESSION_ID`, and an extra
+//! `approve_permission` tool forwards each prompt to the server.
+
+use porter_core::config::PorterConfig;
+use porter_core::db::{self, Database};
+use porter_core::integrations::{Action, IntegrationRegistry};
+use porter_core::models::{CreateTask, NotificationFilter, PermissionDecision, UpdateTask};
+use porter_integrations::register_builtin_integrations;
+use serde_json::{json, Value};
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+const DEFAULT_PROTOCOL_VERSION: &str = "2024-11-05";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub async fn run(config_path: &str, db_path: Option<&str>) -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "warn".into()),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let path = Path::new(config_path);
+    if !path.exists() {
+        anyhow::bail!("Config file not found: {}", config_path);
+    }
+
+    let config = PorterConfig::load(path)?;
+    let db = db::connect(db_path.unwrap_or(&config.instance.db_path)).await?;
+
+    let mut registry = IntegrationRegistry::new();
+    register_builtin_integrations(&mut registry, &config.integrations, db.clone()).await;
+
+    let permission_callback = match (
+        std::env::var("PORTER_URL"),
+        std::env::var("PORTER_SESSION_ID"),
+    ) {
+        (Ok(url), Ok(session_id)) => Some(PermissionCallback {
+            url,
+            session_id,
+            client: reqwest::Client::new(),
+        }),
+        _ => None,
+    };
+
+    let server = McpServer {
+        db,
+        registry,
+        permission_callback,
+    };
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle_message(message).await,
+            Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+
+        if let Some(response) = response {
+            let mut out = serde_json::to_vec(&response)?;
+            out.push(b'\n');
+            stdout.write_all(&out).await?;
+            stdout.flush().await?;
+        }
+    }
+
+    Ok(())
+}
+
+struct McpServer {
+    db: Database,
+    registry: IntegrationRegistry,
+    permission_callback: Option<PermissionCallback>,
+}
+
+/// Where to ask for permission decisions for the session that started us.
+struct PermissionCallback {
+    url: String,
+    session_id: String,
+    client: reqwest::Client,
+}
+
+impl PermissionCallback {
+    /// Ask the server (which may wait for the user) and answer in the format
+    /// Claude's `--permission-prompt-tool` expects. Failures deny.
+    async fn ask(&self, args: &Value) -> Value {
+        let input = args.get("input").cloned().unwrap_or(json!({}));
+        let prompt = json!({
+            "tool_name": args["tool_name"],
+            "input": input,
+            "tool_use_id": args["tool_use_id"],
+        });
+
+        let response = self
+            .client
+            .post(format!("{}/api/agents/{}/permissions", self.url, self.session_id))
+            .json(&prompt)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        let decision = match response {
+            Ok(r) => r.json::<PermissionDecision>().await,
+            Err(e) => Err(e),
+        };
+
+        match decision {
+            Ok(d) if d.allow => json!({ "behavior": "allow", "updatedInput": input }),
+            Ok(d) => json!({
+                "behavior": "deny",
+                "message": d.message.unwrap_or_else(|| "The user denied this tool call.".to_string()),
+            }),
+            Err(e) => {
+                tracing::warn!(error = %e, "Permission request to Porter failed");
+                json!({
+                    "behavior": "deny",
+                    "message": format!("Could not reach Porter to ask for permission: {e}"),
+                })
+            }
+        }
+    }
+}
+
+impl McpServer {
+    /// Handle one JSON-RPC message. Notifications (no `id`) get no response.
+    async fn handle_message(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message["method"].as_str() else {
+            return id.map(|id| error_response(id, INVALID_REQUEST, "Missing method"));
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.tool_definitions() })),
+            "tools/call" => self.call_tool(&params).await,
+            _ if id.is_none() => {
+                // e.g. notifications/initialized, notifications/cancelled
+                tracing::debug!(method, "Ignoring MCP notification");
+                return None;
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {method}"))),
+        };
+
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let protocol_version = params["protocolVersion"]
+            .as_str()
+            .unwrap_or(DEFAULT_PROTOCOL_VERSION);
+
+        json!({
+            "protocolVersion": protocol_version,
+            "capabilities": { "tools": {} },
+            "serverInfo": {
+                "name": "porter",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    async fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params["name"]
+            .as_str()
+            .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+        let args = match params.get("arguments") {
+            Some(Value::Null) | None => json!({}),
+            Some(args) => args.clone(),
+        };
+
+        if !self.tool_definitions().iter().any(|t| t["name"] == name) {
+            return Err((INVALID_PARAMS, format!("Unknown tool: {name}")));
+        }
+
+        // Tool failures are reported in-band so the model can see and react to them.
+        Ok(match self.run_tool(name, args).await {
+            Ok(data) => tool_result(&data, false),
+            Err(e) => tool_result(&Value::String(e.to_string()), true),
+        })
+    }
+
+    fn tool_definitions(&self) -> Vec<Value> {
+        let mut tools = tool_definitions();
+        if self.permission_callback.is_some() {
+            tools.push(json!({
+                "name": "approve_permission",
+                "description": "Ask the Porter user whether a tool call may proceed",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tool_name": { "type": "string" },
+                        "input": { "type": "object" },
+                        "tool_use_id": { "type": "string" }
+                    },
+                    "required": ["tool_name", "input"]
+                }
+            }));
+        }
+        tools
+    }
+
+    async fn run_tool(&self, name: &str, args: Value) -> anyhow::Result<Value> {
+        match name {
+            "approve_permission" => match &self.permission_callback {
+                Some(callback) => Ok(callback.ask(&args).await),
+                None => anyhow::bail!("Permission prompts are not enabled for this session"),
+            },
+            "list_tasks" => {
+                let tasks = self.db.list_tasks(args["status"].as_str()).await?;
+                Ok(serde_json::to_value(tasks)?)
+            }
+            "create_task" => {
+                let input: CreateTask = serde_json::from_value(args)?;
+                let task = self.db.create_task(input).await?;
+                Ok(serde_json::to_value(task)?)
+            }
+            "update_task" => {
+                let id = args["id"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Missing task id"))?
+                    .to_string();
+                let input: UpdateTask = serde_json::from_value(args)?;
+                let task = self
+                    .db
+                    .update_task(&id, input)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("Task not found: {id}"))?;
+                Ok(serde_json::to_value(task)?)
+            }
+            "get_notifications" => {
+                let unread_only = args["unread_only"].as_bool().unwrap_or(false);
+                let filter = NotificationFilter {
+                    read: unread_only.then_some(false),
+                    limit: Some(args["limit"].as_i64().unwrap_or(50)),
+                    ..Default::default()
+                };
+                let notifications = self.db.list_notifications(&filter).await?;
+                Ok(serde_json::to_value(notifications)?)
+            }
+            "list_integrations" => {
+                let integrations: Vec<Value> = self
+                    .registry
+                    .list()
+                    .iter()
+                    .map(|i| {
+                        json!({
+                            "id": i.id(),
+                            "name": i.name(),
+                            "capabilities": i.capabilities(),
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(integrations))
+            }
+            "integration_action" => {
+                let integration_id = args["integration_id"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Missing integration_id"))?;
+                let action = args["action"]
+                    .as_str()
+                    .ok_or_else(|| anyhow::anyhow!("Missing action"))?;
+                let integration = self
+                    .registry
+                    .get(integration_id)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown integration: {integration_id}"))?;
+                let capability = integration
+                    .capabilities()
+                    .into_iter()
+                    .find(|c| c.name == action)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Unknown action '{action}' for {integration_id}")
+                    })?;
+
+                let params = args.get("params").cloned().unwrap_or(json!({}));
+                if let Err(errors) = capability.validate_params(&params) {
+                    anyhow::bail!("Invalid parameters: {}", errors.join("; "));
+                }
+
+                let result = integration
+                    .handle(Action {
+                        name: action.to_string(),
+                        params,
+                    })
+                    .await?;
+                Ok(serde_json::to_value(result)?)
+            }
+            _ => anyhow::bail!("Unknown tool: {name}"),
+        }
+    }
+}
+
+fn tool_result(data: &Value, is_error: bool) -> Value {
+    let text = match data {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    };
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn tool_definitions() -> Vec<Value> {
+    let status_enum = json!(["pending", "in_progress", "completed", "cancelled"]);
+    let priority_enum = json!(["low", "medium", "high", "urgent"]);
+
+    vec![
+        json!({
+            "name": "list_tasks",
+            "description": "List Porter tasks, newest first, optionally filtered by status",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "status": { "type": "string", "enum": status_enum }
+                }
+            }
+        }),
+        json!({
+            "name": "create_task",
+            "description": "Create a new Porter task",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "description": { "type": "string" },
+                    "priority": { "type": "string", "enum": priority_enum },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "due_date": { "type": "string", "description": "RFC 3339 timestamp" }
+                },
+                "required": ["title"]
+            }
+        }),
+        json!({
+            "name": "update_task",
+            "description": "Update fields of an existing Porter task",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string" },
+                    "title": { "type": "string" },
+                    "description": { "type": "string" },
+                    "status": { "type": "string", "enum": status_enum },
+                    "priority": { "type": "string", "enum": priority_enum },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "due_date": { "type": "string", "description": "RFC 3339 timestamp" }
+                },
+                "required": ["id"]
+            }
+        }),
+        json!({
+            "name": "get_notifications",
+            "description": "Read recent Porter notifications, newest first",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "unread_only": { "type": "boolean" },
+                    "limit": { "type": "integer", "minimum": 1 }
+                }
+            }
+        }),
+        json!({
+            "name": "list_integrations",
+            "description": "List Porter's built-in integrations and the actions they support",
+            "inputSchema": { "type": "object", "properties": {} }
+        }),
+        json!({
+            "name": "integration_action",
+            "description": "Invoke an action on a built-in Porter integration",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "integration_id": { "type": "string" },
+                    "action": { "type": "string" },
+                    "params": { "type": "object" }
+                },
+                "required": ["integration_id", "action"]
+            }
+        }),
+    ]
+}
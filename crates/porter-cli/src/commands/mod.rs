@@ -1,4 +1,6 @@
 pub mod agent;
+pub mod db;
+pub mod mcp;
 pub mod serve;
 pub mod status;
 pub mod task;
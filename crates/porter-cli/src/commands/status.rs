@@ -34,6 +34,17 @@ pub async fn run(server: &str) -> anyhow::Result<()> {
             );
             println!("  Sessions: {}", status.active_agent_sessions);
             println!("  Pending:  {} tasks", status.pending_tasks);
+            println!(
+                "  Cost:     ${:.2} today, ${:.2} total",
+                status.usage_today.cost_usd, status.usage_total.cost_usd
+            );
+            println!(
+                "  Tokens:   {} in / {} out today, {} in / {} out total",
+                status.usage_today.input_tokens,
+                status.usage_today.output_tokens,
+                status.usage_total.input_tokens,
+                status.usage_total.output_tokens
+            );
         }
         Ok(resp) => {
             anyhow::bail!("Server returned error: {}", resp.status());
@@ -27,6 +27,20 @@ enum Commands {
         #[command(subcommand)]
         command: AgentCommands,
     },
+    /// Manage the database
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+    /// Run Porter's MCP server over stdio (used by agent sessions)
+    Mcp {
+        /// Path to the config file
+        #[arg(short, long, default_value = "config/home.toml")]
+        config: String,
+        /// Override the database path from the config
+        #[arg(long)]
+        db: Option<String>,
+    },
     /// Show server status
     Status {
         /// Server URL
@@ -35,6 +49,19 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum DbCommands {
+    /// Apply pending schema migrations
+    Migrate {
+        /// Path to the config file
+        #[arg(short, long, default_value = "config/home.toml")]
+        config: String,
+        /// Show applied and pending migrations without applying anything
+        #[arg(long)]
+        status: bool,
+    },
+}
+
 #[derive(Subcommand)]
 enum TaskCommands {
     /// Create a new task
@@ -59,6 +86,12 @@ enum AgentCommands {
     Start {
         /// Prompt for the agent
         prompt: String,
+        /// Model to use instead of the server's default
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Named profile from `[agents.profiles]` in the server config
+        #[arg(short, long)]
+        profile: Option<String>,
     },
     /// List agent sessions
     List {
@@ -66,6 +99,26 @@ enum AgentCommands {
         #[arg(short, long)]
         status: Option<String>,
     },
+    /// Show sessions waiting for a free slot
+    Queue,
+    /// Move a queued session to a new position (1 = next to start)
+    Move {
+        /// Session ID
+        id: String,
+        /// New queue position
+        position: usize,
+    },
+    /// Cancel a running or queued session
+    Cancel {
+        /// Session ID
+        id: String,
+    },
+    /// Delete sessions the server's `[agents.retention]` policy no longer keeps
+    Prune {
+        /// List the sessions that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[tokio::main]
@@ -85,13 +138,39 @@ async fn main() -> anyhow::Result<()> {
             }
         },
         Commands::Agent { command } => match command {
-            AgentCommands::Start { prompt } => {
-                commands::agent::start("http://localhost:3101", &prompt).await?;
+            AgentCommands::Start { prompt, model, profile } => {
+                commands::agent::start(
+                    "http://localhost:3101",
+                    &prompt,
+                    model.as_deref(),
+                    profile.as_deref(),
+                )
+                .await?;
             }
             AgentCommands::List { status } => {
                 commands::agent::list("http://localhost:3101", status.as_deref()).await?;
             }
+            AgentCommands::Queue => {
+                commands::agent::queue("http://localhost:3101").await?;
+            }
+            AgentCommands::Move { id, position } => {
+                commands::agent::move_in_queue("http://localhost:3101", &id, position).await?;
+            }
+            AgentCommands::Cancel { id } => {
+                commands::agent::cancel("http://localhost:3101", &id).await?;
+            }
+            AgentCommands::Prune { dry_run } => {
+                commands::agent::prune("http://localhost:3101", dry_run).await?;
+            }
         },
+        Commands::Db { command } => match command {
+            DbCommands::Migrate { config, status } => {
+                commands::db::migrate(&config, status).await?;
+            }
+        },
+        Commands::Mcp { config, db } => {
+            commands::mcp::run(&config, db.as_deref()).await?;
+        }
         Commands::Status { server } => {
             commands::status::run(&server).await?;
         }
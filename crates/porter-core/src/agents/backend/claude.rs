@@ -0,0 +1,435 @@
+//! The Claude CLI (`claude --print --output-format stream-json`).
+
+use super::{AgentBackend, AgentRun, BackendEvent, Resume, RunRequest};
+use crate::config::McpServerConfig;
+use crate::models::{AgentSession, TokenUsage};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::task::JoinHandle;
+
+/// Runs sessions with the Claude CLI at `binary`.
+pub struct ClaudeCli {
+    binary: String,
+}
+
+impl ClaudeCli {
+    pub fn new(binary: impl Into<String>) -> Self {
+        Self {
+            binary: binary.into(),
+        }
+    }
+
+    async fn start(
+        &self,
+        request: &RunRequest<'_>,
+        resume: Option<&Resume>,
+    ) -> Result<Box<dyn AgentRun>> {
+        let session = request.session;
+        let mcp_config_file = build_mcp_config(request.mcp_servers)?;
+
+        let mut cmd = Command::new(&self.binary);
+        if let Some(resume) = resume {
+            cmd.arg("--resume").arg(&resume.conversation_id);
+            if resume.fork {
+                cmd.arg("--fork-session");
+            }
+        }
+        configure_cmd(&mut cmd, request.cwd, session, &mcp_config_file);
+        if let Some(tool) = request.permission_tool {
+            cmd.arg("--permission-prompt-tool").arg(tool);
+        }
+        // The appended system prompt isn't part of the saved conversation, so
+        // it is passed on every run
+        if let Some(system_prompt) = system_prompt(session, request.mcp_servers) {
+            cmd.arg("--append-system-prompt").arg(system_prompt);
+        }
+        if request.live {
+            cmd.arg("--input-format")
+                .arg("stream-json")
+                .stdin(Stdio::piped());
+        } else {
+            cmd.arg(request.prompt);
+        }
+
+        tracing::info!(
+            session_id = %session.id,
+            resume = ?resume,
+            cwd = %request.cwd.display(),
+            model = %session.model,
+            mcp = ?request.mcp_servers.keys().collect::<Vec<_>>(),
+            skip_permissions = session.dangerously_skip_permissions,
+            live = request.live,
+            "Starting Claude process"
+        );
+
+        let mut child = cmd.spawn()?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to capture stdout"))?;
+        let stdin = if request.live {
+            let mut stdin = child
+                .stdin
+                .take()
+                .ok_or_else(|| anyhow::anyhow!("Failed to capture stdin"))?;
+            write_user_message(&mut stdin, request.prompt).await?;
+            Some(stdin)
+        } else {
+            None
+        };
+
+        Ok(Box::new(ClaudeRun {
+            session_id: session.id.clone(),
+            lines: BufReader::new(stdout).lines(),
+            stderr: child
+                .stderr
+                .take()
+                .map(|stderr| tokio::spawn(log_stderr(stderr, session.id.clone()))),
+            child,
+            stdin,
+            pending: VecDeque::new(),
+            _mcp_config: mcp_config_file,
+        }))
+    }
+}
+
+#[async_trait]
+impl AgentBackend for ClaudeCli {
+    async fn spawn(&self, request: &RunRequest<'_>) -> Result<Box<dyn AgentRun>> {
+        self.start(request, None).await
+    }
+
+    async fn resume(&self, request: &RunRequest<'_>, resume: &Resume) -> Result<Box<dyn AgentRun>> {
+        self.start(request, Some(resume)).await
+    }
+}
+
+/// A Claude process and its output stream.
+struct ClaudeRun {
+    session_id: String,
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+    /// Logs stderr as it arrives, so a long-lived process (or the MCP
+    /// servers sharing its stderr) can't fill the pipe and stall.
+    stderr: Option<JoinHandle<()>>,
+    /// Open while the process takes stream-json input.
+    stdin: Option<ChildStdin>,
+    /// Events from a line that had several content blocks.
+    pending: VecDeque<BackendEvent>,
+    /// Deleted when the run is dropped, so it outlives the process.
+    _mcp_config: Option<tempfile::NamedTempFile>,
+}
+
+#[async_trait]
+impl AgentRun for ClaudeRun {
+    async fn next_event(&mut self) -> Result<Option<BackendEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            let Some(line) = self.lines.next_line().await? else {
+                return Ok(None);
+            };
+            // Anything that isn't JSON (or an event we don't use) is skipped
+            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&line) {
+                self.parse(&parsed, &line);
+            }
+        }
+    }
+
+    async fn send(&mut self, message: &str) -> Result<()> {
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Claude process is not taking input"))?;
+        write_user_message(stdin, message).await
+    }
+
+    fn close_input(&mut self) {
+        // Claude exits once its stdin is closed
+        self.stdin = None;
+    }
+
+    async fn cancel(&mut self) {
+        let _ = self.child.kill().await;
+        self.flush_stderr().await;
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        let status = self.child.wait().await?;
+        self.flush_stderr().await;
+
+        if !status.success() {
+            anyhow::bail!("Claude process exited with status: {}", status);
+        }
+        Ok(())
+    }
+}
+
+impl ClaudeRun {
+    /// Let the stderr logger catch up once the process has exited. MCP
+    /// servers it started may hold the pipe open, so it isn't waited for
+    /// long.
+    async fn flush_stderr(&mut self) {
+        if let Some(mut task) = self.stderr.take() {
+            if tokio::time::timeout(Duration::from_secs(1), &mut task).await.is_err() {
+                task.abort();
+            }
+        }
+    }
+
+    /// Queue the events in one line of stream-json output.
+    fn parse(&mut self, parsed: &serde_json::Value, line: &str) {
+        match parsed["type"].as_str().unwrap_or("") {
+            "system" if parsed["subtype"].as_str() == Some("init") => {
+                if let Some(servers) = parsed["mcp_servers"].as_array() {
+                    let names: Vec<&str> = servers.iter().filter_map(|s| s.as_str()).collect();
+                    tracing::info!(
+                        session_id = %self.session_id,
+                        mcp_servers = ?names,
+                        "Claude session initialized"
+                    );
+                }
+                if let Some(sid) = parsed["session_id"].as_str() {
+                    self.pending.push_back(BackendEvent::Started {
+                        conversation_id: sid.to_string(),
+                        detail: line.to_string(),
+                    });
+                }
+            }
+            "assistant" => {
+                for block in parsed["message"]["content"].as_array().into_iter().flatten() {
+                    let event = match block["type"].as_str() {
+                        Some("text") => block["text"].as_str().map(|t| BackendEvent::Text(t.into())),
+                        Some("thinking") => block["thinking"]
+                            .as_str()
+                            .map(|t| BackendEvent::Thinking(t.into())),
+                        Some("tool_use") => block["name"].as_str().map(|name| BackendEvent::ToolUse {
+                            id: block["id"].as_str().map(String::from),
+                            name: name.to_string(),
+                            input: block["input"].clone(),
+                        }),
+                        _ => None,
+                    };
+                    self.pending.extend(event);
+                }
+            }
+            "user" => {
+                // Tool results come back to Claude as user-role content blocks
+                for block in parsed["message"]["content"].as_array().into_iter().flatten() {
+                    if block["type"].as_str() == Some("tool_result") {
+                        self.pending.push_back(BackendEvent::ToolResult {
+                            tool_use_id: block["tool_use_id"].as_str().map(String::from),
+                            content: tool_result_text(&block["content"]),
+                            is_error: block["is_error"].as_bool().unwrap_or(false),
+                        });
+                    }
+                }
+            }
+            "result" => {
+                // Error results (e.g. a failed resume) list what went wrong
+                let error = (parsed["is_error"].as_bool() == Some(true)).then(|| {
+                    parsed["errors"]
+                        .as_array()
+                        .map(|arr| {
+                            arr.iter()
+                                .filter_map(|e| e.as_str())
+                                .collect::<Vec<_>>()
+                                .join("; ")
+                        })
+                        .unwrap_or_else(|| "Unknown error".to_string())
+                });
+                self.pending.push_back(BackendEvent::TurnComplete {
+                    usage: parse_usage(parsed),
+                    text: parsed["result"].as_str().map(String::from),
+                    error,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A tool result's content is either a plain string or a list of typed blocks.
+fn tool_result_text(content: &serde_json::Value) -> String {
+    match content {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(parts) => parts
+            .iter()
+            .map(|p| match p["type"].as_str() {
+                Some("text") => p["text"].as_str().unwrap_or_default().to_string(),
+                Some(other) => format!("[{other}]"),
+                None => String::new(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Build a temporary MCP config JSON file for the Claude CLI.
+fn build_mcp_config(
+    mcp_servers: &HashMap<String, McpServerConfig>,
+) -> Result<Option<tempfile::NamedTempFile>> {
+    if mcp_servers.is_empty() {
+        return Ok(None);
+    }
+
+    let mut servers = serde_json::Map::new();
+
+    for (name, config) in mcp_servers {
+        let mut env_map = serde_json::Map::new();
+        for (key, value) in &config.env {
+            let resolved = if let Some(env_key) = value.strip_prefix("env:") {
+                std::env::var(env_key).unwrap_or_default()
+            } else {
+                value.clone()
+            };
+            env_map.insert(key.clone(), serde_json::Value::String(resolved));
+        }
+
+        let server = serde_json::json!({
+            "command": config.command,
+            "args": config.args,
+            "env": env_map,
+        });
+
+        servers.insert(name.clone(), server);
+    }
+
+    let mcp_json = serde_json::json!({ "mcpServers": servers });
+
+    let file = tempfile::Builder::new()
+        .prefix("porter-mcp-")
+        .suffix(".json")
+        .tempfile()?;
+
+    // Write and flush in a block so the BufWriter is dropped before we move `file`
+    {
+        use std::io::Write;
+        let mut f = std::io::BufWriter::new(&file);
+        serde_json::to_writer(&mut f, &mcp_json)?;
+        f.flush()?;
+    }
+
+    tracing::debug!(
+        path = %file.path().display(),
+        servers = ?mcp_servers.keys().collect::<Vec<_>>(),
+        "Wrote MCP config"
+    );
+
+    Ok(Some(file))
+}
+
+/// Apply common flags to a Claude command: CWD, --dangerously-skip-permissions,
+/// MCP config, output format, and stdio piping.
+fn configure_cmd(
+    cmd: &mut Command,
+    cwd: &std::path::Path,
+    session: &AgentSession,
+    mcp_config_file: &Option<tempfile::NamedTempFile>,
+) {
+    // The tool lists are variadic in the Claude CLI, so they go before any
+    // flag whose value (or the trailing prompt) could be taken as a tool name.
+    if !session.allowed_tools.is_empty() {
+        cmd.arg("--allowedTools").arg(session.allowed_tools.join(","));
+    }
+    if !session.disallowed_tools.is_empty() {
+        cmd.arg("--disallowedTools").arg(session.disallowed_tools.join(","));
+    }
+
+    cmd.current_dir(cwd)
+        .arg("--print")
+        .arg("--output-format")
+        .arg("stream-json")
+        .arg("--verbose")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    cmd.arg("--model").arg(&session.model);
+    if let Some(max_turns) = session.max_turns {
+        cmd.arg("--max-turns").arg(max_turns.to_string());
+    }
+    if let Some(mode) = session.permission_mode {
+        cmd.arg("--permission-mode").arg(mode.as_str());
+    }
+
+    if session.dangerously_skip_permissions {
+        cmd.arg("--dangerously-skip-permissions");
+    }
+
+    if let Some(ref config_file) = mcp_config_file {
+        cmd.arg("--mcp-config").arg(config_file.path());
+    }
+}
+
+/// Extract token usage, cost and duration from a `result` event.
+fn parse_usage(result: &serde_json::Value) -> Option<TokenUsage> {
+    let usage = &result["usage"];
+    if !usage.is_object() && result["total_cost_usd"].is_null() {
+        return None;
+    }
+
+    Some(TokenUsage {
+        input_tokens: usage["input_tokens"].as_i64().unwrap_or(0),
+        output_tokens: usage["output_tokens"].as_i64().unwrap_or(0),
+        cache_creation_input_tokens: usage["cache_creation_input_tokens"].as_i64().unwrap_or(0),
+        cache_read_input_tokens: usage["cache_read_input_tokens"].as_i64().unwrap_or(0),
+        // Older CLI versions report `cost_usd` instead of `total_cost_usd`
+        cost_usd: result["total_cost_usd"]
+            .as_f64()
+            .or_else(|| result["cost_usd"].as_f64())
+            .unwrap_or(0.0),
+        duration_ms: result["duration_ms"].as_i64().unwrap_or(0),
+    })
+}
+
+/// Log each line of stderr until it closes.
+async fn log_stderr(stderr: ChildStderr, session_id: String) {
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if !line.is_empty() {
+            tracing::warn!(session_id = %session_id, stderr = %line, "Claude stderr");
+        }
+    }
+}
+
+/// Tell the agent which MCP servers it has, alongside any instructions the
+/// session was started with.
+fn system_prompt(
+    session: &AgentSession,
+    mcp_servers: &HashMap<String, McpServerConfig>,
+) -> Option<String> {
+    let mut notes = Vec::new();
+    if !mcp_servers.is_empty() {
+        let server_list: Vec<&str> = mcp_servers.keys().map(|s| s.as_str()).collect();
+        notes.push(format!(
+            "You have access to MCP servers: {}. Use them when relevant.",
+            server_list.join(", ")
+        ));
+    }
+    notes.extend(session.append_system_prompt.clone());
+    (!notes.is_empty()).then(|| notes.join("\n\n"))
+}
+
+/// Write one user turn in Claude's stream-json input format.
+async fn write_user_message(stdin: &mut ChildStdin, text: &str) -> Result<()> {
+    let message = serde_json::json!({
+        "type": "user",
+        "message": {
+            "role": "user",
+            "content": [{ "type": "text", "text": text }],
+        },
+    });
+    let mut line = serde_json::to_vec(&message)?;
+    line.push(b'\n');
+    stdin.write_all(&line).await?;
+    stdin.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Agent implementations sessions can run on.
+//!
+//! The Claude CLI is the default backend; others are configured under
+//! `[agents.backends]` and picked per profile or session. A backend turns a
+//! session into a stream of [`BackendEvent`]s, which the manager records in
+//! the transcript the same way whatever produced them.
+
+mod claude;
+mod openai;
+
+pub use claude::ClaudeCli;
+pub use openai::OpenAiCompatible;
+
+use crate::config::McpServerConfig;
+use crate::models::{AgentSession, TokenUsage};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Starts agent runs for sessions.
+#[async_trait]
+pub trait AgentBackend: Send + Sync {
+    /// Start a new conversation with `request.prompt`.
+    async fn spawn(&self, request: &RunRequest<'_>) -> Result<Box<dyn AgentRun>>;
+
+    /// Continue the conversation `resume` (reported earlier in a
+    /// [`BackendEvent::Started`]) with `request.prompt`.
+    async fn resume(&self, request: &RunRequest<'_>, resume: &Resume) -> Result<Box<dyn AgentRun>>;
+}
+
+/// One running conversation.
+///
+/// A run answers its prompt and ends, unless it was started with
+/// `RunRequest::live`: then it stays up for messages passed to
+/// [`AgentRun::send`] until its input is closed.
+#[async_trait]
+pub trait AgentRun: Send {
+    /// The next event, or `None` once the run has ended. Must be cancel-safe:
+    /// it is raced against timeouts and incoming messages.
+    async fn next_event(&mut self) -> Result<Option<BackendEvent>>;
+
+    /// Start another turn of a live run with `message`.
+    async fn send(&mut self, message: &str) -> Result<()>;
+
+    /// End a live run once its current turn is done.
+    fn close_input(&mut self);
+
+    /// Stop the run immediately.
+    async fn cancel(&mut self);
+
+    /// Wait for an ended run to shut down, reporting how it exited.
+    async fn finish(&mut self) -> Result<()>;
+}
+
+/// What a backend needs to start a run.
+pub struct RunRequest<'a> {
+    pub session: &'a AgentSession,
+    pub cwd: &'a Path,
+    /// The session prompt, or a follow-up message when resuming.
+    pub prompt: &'a str,
+    pub mcp_servers: &'a HashMap<String, McpServerConfig>,
+    /// MCP tool to ask for permission with, if prompts go through Porter.
+    pub permission_tool: Option<&'a str>,
+    /// Keep the run up for follow-ups; see [`AgentRun`].
+    pub live: bool,
+}
+
+/// The conversation a run continues.
+#[derive(Debug)]
+pub struct Resume {
+    /// As reported by the backend (stored as the session's `claude_session_id`).
+    pub conversation_id: String,
+    /// Continue it as a new conversation, leaving the original to the
+    /// session it was forked from.
+    pub fork: bool,
+}
+
+/// Something that happened in a run, in the order it happened.
+#[derive(Debug, Clone)]
+pub enum BackendEvent {
+    /// The conversation is under way. `detail` is kept in the transcript.
+    Started {
+        conversation_id: String,
+        detail: String,
+    },
+    Text(String),
+    Thinking(String),
+    ToolUse {
+        id: Option<String>,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: Option<String>,
+        content: String,
+        is_error: bool,
+    },
+    /// A turn is over. `text` is its final answer, used if no `Text` event
+    /// carried one; `error` fails the run.
+    TurnComplete {
+        usage: Option<TokenUsage>,
+        text: Option<String>,
+        error: Option<String>,
+    },
+}
@@ -0,0 +1,241 @@
+//! Any server with an OpenAI-style `/chat/completions` endpoint, such as a
+//! local llama.cpp, Ollama or vLLM instance.
+//!
+//! These models only chat: MCP servers, tools and permission settings don't
+//! apply. Each turn sends the session's transcript so far, so the session id
+//! doubles as the conversation id and forks simply continue from their copy.
+
+use super::{AgentBackend, AgentRun, BackendEvent, Resume, RunRequest};
+use crate::db::Database;
+use crate::models::TokenUsage;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Instant;
+
+/// Runs sessions against the chat completions endpoint under `base_url`.
+pub struct OpenAiCompatible {
+    endpoint: Endpoint,
+    /// Model to request instead of the session's.
+    model: Option<String>,
+    db: Database,
+}
+
+#[derive(Clone)]
+struct Endpoint {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatible {
+    /// `api_key` may name an environment variable as `env:VAR`.
+    pub fn new(
+        db: Database,
+        base_url: &str,
+        api_key: Option<String>,
+        model: Option<String>,
+    ) -> Self {
+        let api_key = api_key.map(|key| match key.strip_prefix("env:") {
+            Some(var) => std::env::var(var).unwrap_or_default(),
+            None => key,
+        });
+
+        Self {
+            endpoint: Endpoint {
+                client: reqwest::Client::new(),
+                url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+                api_key,
+            },
+            model,
+            db,
+        }
+    }
+}
+
+#[async_trait]
+impl AgentBackend for OpenAiCompatible {
+    async fn spawn(&self, request: &RunRequest<'_>) -> Result<Box<dyn AgentRun>> {
+        let session = request.session;
+        let model = self.model.clone().unwrap_or_else(|| session.model.clone());
+
+        tracing::info!(
+            session_id = %session.id,
+            url = %self.endpoint.url,
+            model = %model,
+            live = request.live,
+            "Starting chat completions run"
+        );
+
+        let mut run = ChatRun {
+            endpoint: self.endpoint.clone(),
+            db: self.db.clone(),
+            session_id: session.id.clone(),
+            model,
+            system_prompt: session.append_system_prompt.clone(),
+            pending: VecDeque::new(),
+            request: None,
+            live: request.live,
+        };
+        run.pending.push_back(BackendEvent::Started {
+            conversation_id: session.id.clone(),
+            detail: json!({
+                "type": "system",
+                "subtype": "init",
+                "backend": "openai",
+                "model": run.model,
+            })
+            .to_string(),
+        });
+        run.start_turn();
+        Ok(Box::new(run))
+    }
+
+    /// The transcript already holds the conversation, forked or not.
+    async fn resume(&self, request: &RunRequest<'_>, _resume: &Resume) -> Result<Box<dyn AgentRun>> {
+        self.spawn(request).await
+    }
+}
+
+type Turn = Pin<Box<dyn Future<Output = Result<Vec<BackendEvent>>> + Send>>;
+
+/// One session's chat. The prompt (or follow-up) is already the last user
+/// message in the transcript when a turn starts.
+struct ChatRun {
+    endpoint: Endpoint,
+    db: Database,
+    session_id: String,
+    model: String,
+    system_prompt: Option<String>,
+    pending: VecDeque<BackendEvent>,
+    /// The turn in progress, kept here so `next_event` stays cancel-safe.
+    request: Option<Turn>,
+    /// Cleared when input is closed.
+    live: bool,
+}
+
+impl ChatRun {
+    fn start_turn(&mut self) {
+        self.request = Some(Box::pin(complete(
+            self.endpoint.clone(),
+            self.db.clone(),
+            self.session_id.clone(),
+            self.model.clone(),
+            self.system_prompt.clone(),
+        )));
+    }
+}
+
+#[async_trait]
+impl AgentRun for ChatRun {
+    async fn next_event(&mut self) -> Result<Option<BackendEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            match self.request.as_mut() {
+                Some(turn) => {
+                    let events = turn.await;
+                    self.request = None;
+                    self.pending.extend(events?);
+                }
+                // Nothing happens between turns until a message is sent
+                None if self.live => std::future::pending::<()>().await,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    async fn send(&mut self, _message: &str) -> Result<()> {
+        if self.request.is_some() {
+            anyhow::bail!("Chat is still answering the previous message");
+        }
+        self.start_turn();
+        Ok(())
+    }
+
+    fn close_input(&mut self) {
+        self.live = false;
+    }
+
+    async fn cancel(&mut self) {
+        self.request = None;
+        self.pending.clear();
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Ask for the next assistant message given the transcript so far.
+async fn complete(
+    endpoint: Endpoint,
+    db: Database,
+    session_id: String,
+    model: String,
+    system_prompt: Option<String>,
+) -> Result<Vec<BackendEvent>> {
+    let mut messages: Vec<serde_json::Value> = system_prompt
+        .into_iter()
+        .map(|prompt| json!({ "role": "system", "content": prompt }))
+        .collect();
+    messages.extend(
+        db.get_agent_messages(&session_id)
+            .await?
+            .into_iter()
+            .filter(|m| m.content_type == "text" && matches!(m.role.as_str(), "user" | "assistant"))
+            .map(|m| json!({ "role": m.role, "content": m.content })),
+    );
+
+    let started = Instant::now();
+    let mut request = endpoint.client.post(&endpoint.url).json(&json!({
+        "model": model,
+        "messages": messages,
+        "stream": false,
+    }));
+    if let Some(key) = endpoint.api_key.as_deref().filter(|k| !k.is_empty()) {
+        request = request.bearer_auth(key);
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Chat completions request failed with status {status}: {}", body.trim());
+    }
+    let body: serde_json::Value = response.json().await?;
+
+    let usage = &body["usage"];
+    let usage = usage.is_object().then(|| TokenUsage {
+        input_tokens: usage["prompt_tokens"].as_i64().unwrap_or(0),
+        output_tokens: usage["completion_tokens"].as_i64().unwrap_or(0),
+        cache_creation_input_tokens: 0,
+        cache_read_input_tokens: 0,
+        cost_usd: 0.0,
+        duration_ms: started.elapsed().as_millis() as i64,
+    });
+
+    let mut events = Vec::new();
+    let message = &body["choices"][0]["message"];
+    // Reasoning models served by e.g. vLLM or DeepSeek report their thinking
+    // separately
+    if let Some(thinking) = message["reasoning_content"].as_str().filter(|t| !t.is_empty()) {
+        events.push(BackendEvent::Thinking(thinking.to_string()));
+    }
+    let error = match message["content"].as_str() {
+        Some(text) => {
+            events.push(BackendEvent::Text(text.to_string()));
+            None
+        }
+        None => Some("Chat completions response had no message content".to_string()),
+    };
+    events.push(BackendEvent::TurnComplete {
+        usage,
+        text: None,
+        error,
+    });
+    Ok(events)
+}
@@ -0,0 +1,295 @@
+use super::AgentEvent;
+use crate::config::BudgetConfig;
+use crate::db::Database;
+use crate::models::AgentSession;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Local, NaiveTime, Utc};
+use tokio::sync::broadcast;
+
+/// Returned (via `anyhow`) when a spending limit blocks an agent action.
+#[derive(Debug)]
+pub struct BudgetExceeded(pub String);
+
+impl std::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Enforces `[agents.budget]` limits against recorded usage.
+///
+/// Claude reports cost once per turn (in its `result` event), so limits are
+/// checked before work starts and again each time a turn's cost is recorded;
+/// a run whose session goes over its cap is cancelled there and then, even
+/// if it would otherwise carry on with further turns.
+#[derive(Clone)]
+pub(crate) struct Budget {
+    config: BudgetConfig,
+    db: Database,
+    event_tx: broadcast::Sender<AgentEvent>,
+}
+
+impl Budget {
+    pub fn new(
+        config: BudgetConfig,
+        db: Database,
+        event_tx: broadcast::Sender<AgentEvent>,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            event_tx,
+        }
+    }
+
+    /// Refuse new work once the daily or monthly budget is spent.
+    pub async fn check_global(&self) -> Result<()> {
+        for (label, limit, since) in self.periods() {
+            let Some(limit) = limit else { continue };
+            let spent = self.db.agent_usage_summary(Some(since)).await?.usage.cost_usd;
+            if spent >= limit {
+                return Err(BudgetExceeded(format!(
+                    "{label} budget of ${limit:.2} reached (${spent:.2} spent)"
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuse follow-ups on a session that has used up its own cap.
+    pub fn check_session(&self, session: &AgentSession) -> Result<()> {
+        if let Some(cap) = self.config.per_session_usd {
+            if session.usage.cost_usd >= cap {
+                return Err(BudgetExceeded(format!(
+                    "Session budget of ${cap:.2} reached (${:.2} spent)",
+                    session.usage.cost_usd
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Called after a run costing `run_cost` was recorded for `session_id`.
+    /// Sends a notification for every limit that just crossed the warning
+    /// ratio or was exhausted, and fails if the session is now over its cap.
+    pub async fn after_run(&self, session_id: &str, run_cost: f64) -> Result<()> {
+        if run_cost <= 0.0 {
+            return Ok(());
+        }
+
+        for (label, limit, since) in self.periods() {
+            let Some(limit) = limit else { continue };
+            let spent = self.db.agent_usage_summary(Some(since)).await?.usage.cost_usd;
+            self.notify_crossings(label, limit, spent - run_cost, spent)
+                .await;
+        }
+
+        let Some(cap) = self.config.per_session_usd else {
+            return Ok(());
+        };
+        let Some(session) = self.db.get_agent_session(session_id).await? else {
+            return Ok(());
+        };
+        let spent = session.usage.cost_usd;
+        let short_id = &session_id[..8.min(session_id.len())];
+        self.notify_crossings(&format!("Session {short_id}"), cap, spent - run_cost, spent)
+            .await;
+        self.check_session(&session)
+    }
+
+    async fn notify_crossings(&self, label: &str, limit: f64, before: f64, after: f64) {
+        let warn_at = limit * self.config.warn_ratio;
+        let (notification_type, message) = if before < limit && after >= limit {
+            (
+                "budget_exceeded",
+                format!("{label} budget of ${limit:.2} exhausted (${after:.2} spent)"),
+            )
+        } else if before < warn_at && after >= warn_at {
+            (
+                "budget_warning",
+                format!(
+                    "{label} budget {:.0}% used (${after:.2} of ${limit:.2})",
+                    after / limit * 100.0
+                ),
+            )
+        } else {
+            return;
+        };
+
+        match self
+            .db
+            .create_notification(notification_type, &message, None)
+            .await
+        {
+            Ok(notification) => {
+                let _ = self.event_tx.send(AgentEvent::Notification(notification));
+            }
+            Err(e) => tracing::error!(error = %e, "Failed to store budget notification"),
+        }
+    }
+
+    fn periods(&self) -> [(&'static str, Option<f64>, DateTime<Utc>); 2] {
+        let today = Local::now().date_naive();
+        let month_start = today.with_day(1).unwrap_or(today);
+        [
+            ("Daily", self.config.daily_usd, local_midnight(today)),
+            ("Monthly", self.config.monthly_usd, local_midnight(month_start)),
+        ]
+    }
+}
+
+fn local_midnight(date: chrono::NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or_else(|| date.and_time(NaiveTime::MIN).and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{NotificationFilter, TokenUsage};
+
+    struct Harness {
+        budget: Budget,
+        db: Database,
+        session_id: String,
+        _dir: tempfile::TempDir,
+    }
+
+    impl Harness {
+        async fn new(config: BudgetConfig) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let db_path = dir.path().join("porter.db");
+            let db = crate::db::connect(&db_path.to_string_lossy()).await.unwrap();
+            let session = db
+                .create_agent_session(AgentSession::new("x", "model"))
+                .await
+                .unwrap();
+            let budget = Budget::new(config, db.clone(), broadcast::channel(16).0);
+            Self {
+                budget,
+                db,
+                session_id: session.id,
+                _dir: dir,
+            }
+        }
+
+        /// Record a run costing `cost_usd` and check it against the budget.
+        async fn run(&self, cost_usd: f64) -> Result<()> {
+            let usage = TokenUsage {
+                cost_usd,
+                ..Default::default()
+            };
+            self.db.record_agent_usage(&self.session_id, &usage).await.unwrap();
+            self.budget.after_run(&self.session_id, cost_usd).await
+        }
+
+        /// Types of the notifications sent since the last call.
+        async fn notified(&self) -> Vec<String> {
+            let filter = NotificationFilter {
+                read: Some(false),
+                ..Default::default()
+            };
+            let notifications = self.db.list_notifications(&filter).await.unwrap();
+            self.db.mark_all_notifications_read(None).await.unwrap();
+            notifications.into_iter().map(|n| n.notification_type).collect()
+        }
+    }
+
+    fn config() -> BudgetConfig {
+        BudgetConfig {
+            warn_ratio: 0.75,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn notifies_daily_crossings_once() {
+        let h = Harness::new(BudgetConfig {
+            daily_usd: Some(1.0),
+            ..config()
+        })
+        .await;
+
+        h.run(0.5).await.unwrap();
+        assert!(h.notified().await.is_empty());
+        h.run(0.25).await.unwrap();
+        assert_eq!(h.notified().await, ["budget_warning"]);
+        h.run(0.125).await.unwrap();
+        assert!(h.notified().await.is_empty());
+        h.run(0.25).await.unwrap();
+        assert_eq!(h.notified().await, ["budget_exceeded"]);
+        h.run(0.25).await.unwrap();
+        assert!(h.notified().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn notifies_each_period_separately() {
+        let h = Harness::new(BudgetConfig {
+            daily_usd: Some(1.0),
+            monthly_usd: Some(2.0),
+            ..config()
+        })
+        .await;
+
+        h.run(1.0).await.unwrap();
+        assert_eq!(h.notified().await, ["budget_exceeded"]);
+        h.run(0.5).await.unwrap();
+        assert_eq!(h.notified().await, ["budget_warning"]);
+        h.run(0.5).await.unwrap();
+        assert_eq!(h.notified().await, ["budget_exceeded"]);
+        h.run(0.5).await.unwrap();
+        assert!(h.notified().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn jumping_past_a_limit_only_reports_it_exhausted() {
+        let h = Harness::new(BudgetConfig {
+            daily_usd: Some(1.0),
+            ..config()
+        })
+        .await;
+
+        h.run(0.5).await.unwrap();
+        h.run(1.0).await.unwrap();
+        assert_eq!(h.notified().await, ["budget_exceeded"]);
+    }
+
+    #[tokio::test]
+    async fn fails_runs_over_the_session_cap() {
+        let h = Harness::new(BudgetConfig {
+            per_session_usd: Some(1.0),
+            ..config()
+        })
+        .await;
+
+        h.run(0.75).await.unwrap();
+        assert_eq!(h.notified().await, ["budget_warning"]);
+        let err = h.run(0.5).await.unwrap_err();
+        assert!(err.is::<BudgetExceeded>());
+        assert_eq!(h.notified().await, ["budget_exceeded"]);
+        assert!(h.run(0.25).await.unwrap_err().is::<BudgetExceeded>());
+        assert!(h.notified().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn free_runs_notify_nothing() {
+        let h = Harness::new(BudgetConfig {
+            daily_usd: Some(0.5),
+            per_session_usd: Some(0.5),
+            ..config()
+        })
+        .await;
+
+        h.run(1.0).await.unwrap_err();
+        assert_eq!(h.notified().await.len(), 2);
+        h.run(0.0).await.unwrap();
+        assert!(h.notified().await.is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+use crate::models::SessionFile;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::path::{Component, Path, PathBuf};
+
+/// Listings are cut off after this many files (by path), so pointing a
+/// session at a large tree doesn't produce an enormous response.
+const MAX_FILES: usize = 5000;
+/// Larger files are left out of snapshots.
+const MAX_SNAPSHOT_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// Snapshot directory names: the UTC time they were taken.
+const SNAPSHOT_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// The first `MAX_FILES` files under `root`, by path relative to it.
+pub(crate) fn list(root: &Path) -> Result<Vec<SessionFile>> {
+    let mut files = list_all(root)?;
+    files.truncate(MAX_FILES);
+    Ok(files)
+}
+
+/// Every file under `root`, by path relative to it. Symlinks and `.git` are
+/// skipped, as is anything that can't be read (with a warning).
+fn list_all(root: &Path) -> Result<Vec<SessionFile>> {
+    let mut files = Vec::new();
+    if root.is_dir() {
+        walk(root, root, &mut files)?;
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<SessionFile>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let (entry, meta) = match entry.and_then(|entry| entry.metadata().map(|m| (entry, m))) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!(dir = %dir.display(), error = %e, "Skipping unreadable file");
+                continue;
+            }
+        };
+        let path = entry.path();
+        if meta.is_dir() {
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            if let Err(e) = walk(root, &path, files) {
+                tracing::warn!(dir = %path.display(), error = %e, "Skipping unreadable directory");
+            }
+        } else if meta.is_file() {
+            let relative = path.strip_prefix(root)?;
+            files.push(SessionFile {
+                path: relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                size: meta.len(),
+                modified: meta.modified().map(DateTime::<Utc>::from).unwrap_or_default(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The file at `relative` under `root`, if there is one and it doesn't lead
+/// outside `root` (through `..` or a symlink).
+pub(crate) fn resolve(root: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    let root = std::fs::canonicalize(root).ok()?;
+    let path = std::fs::canonicalize(root.join(relative)).ok()?;
+    (path.starts_with(&root) && path.is_file()).then_some(path)
+}
+
+/// Names of the snapshots in `dir`, oldest first.
+pub(crate) fn snapshots(dir: &Path) -> Result<Vec<String>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<String> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| NaiveDateTime::parse_from_str(name, SNAPSHOT_FORMAT).is_ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Copy files in `root` changed since the latest snapshot in `dir` (or since
+/// `since`, if there are none yet) into a new snapshot there. Returns the
+/// snapshot's name and file count, or `None` if nothing changed.
+pub(crate) fn snapshot(
+    root: &Path,
+    dir: &Path,
+    since: DateTime<Utc>,
+) -> Result<Option<(String, usize)>> {
+    let since = snapshots(dir)?
+        .last()
+        .and_then(|name| NaiveDateTime::parse_from_str(name, SNAPSHOT_FORMAT).ok())
+        .map(|t| t.and_utc())
+        .unwrap_or(since);
+
+    let now = Utc::now();
+    let changed: Vec<SessionFile> = list_all(root)?
+        .into_iter()
+        .filter(|f| f.modified >= since && f.size <= MAX_SNAPSHOT_FILE_BYTES)
+        .collect();
+
+    let name = now.format(SNAPSHOT_FORMAT).to_string();
+    let target = dir.join(&name);
+    let mut copied = 0;
+    for file in &changed {
+        let dest = target.join(&file.path);
+        let copy = dest
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::copy(root.join(&file.path), &dest));
+        match copy {
+            Ok(_) => copied += 1,
+            Err(e) => {
+                tracing::warn!(file = %file.path, error = %e, "Leaving file out of snapshot")
+            }
+        }
+    }
+    if copied == 0 {
+        let _ = std::fs::remove_dir_all(&target);
+        return Ok(None);
+    }
+    Ok(Some((name, copied)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn paths(files: &[SessionFile]) -> Vec<&str> {
+        files.iter().map(|f| f.path.as_str()).collect()
+    }
+
+    #[test]
+    fn lists_files_by_relative_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/bin")).unwrap();
+        fs::create_dir_all(root.join(".git/objects")).unwrap();
+        fs::write(root.join("src/bin/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        fs::write(root.join("README.md"), "# readme").unwrap();
+        fs::write(root.join(".git/HEAD"), "ref").unwrap();
+
+        let files = list(root).unwrap();
+        assert_eq!(paths(&files), ["README.md", "src/bin/main.rs", "src/lib.rs"]);
+        assert_eq!(files[0].size, 8);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret"), "").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("dir")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret"), dir.path().join("file"))
+            .unwrap();
+
+        assert!(list(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn cuts_listings_off_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in (0..=MAX_FILES).rev() {
+            fs::write(dir.path().join(format!("{i:05}")), "").unwrap();
+        }
+
+        let files = list(dir.path()).unwrap();
+        assert_eq!(files.len(), MAX_FILES);
+        assert_eq!(files.last().unwrap().path, format!("{:05}", MAX_FILES - 1));
+    }
+
+    #[test]
+    fn lists_nothing_for_a_missing_root() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(list(&dir.path().join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn snapshots_every_changed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("work");
+        let snapshots_dir = dir.path().join("snapshots");
+        fs::create_dir(&root).unwrap();
+        for i in 0..=MAX_FILES {
+            fs::write(root.join(format!("{i:05}")), "").unwrap();
+        }
+
+        let since = Utc::now() - chrono::Duration::minutes(1);
+        let (name, count) = snapshot(&root, &snapshots_dir, since).unwrap().unwrap();
+        assert_eq!(count, MAX_FILES + 1);
+        assert_eq!(snapshots(&snapshots_dir).unwrap(), [name.as_str()]);
+        assert!(snapshots_dir.join(name).join(format!("{MAX_FILES:05}")).is_file());
+    }
+
+    /// A root holding `dir/file.txt`, next to `secret.txt` outside it.
+    fn resolve_scratch() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("dir")).unwrap();
+        fs::write(root.join("dir/file.txt"), "").unwrap();
+        fs::write(dir.path().join("secret.txt"), "").unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn resolves_files_under_the_root() {
+        let (_dir, root) = resolve_scratch();
+
+        let expected = root.join("dir/file.txt").canonicalize().unwrap();
+        assert_eq!(resolve(&root, "dir/file.txt"), Some(expected));
+        assert_eq!(resolve(&root, "dir"), None);
+        assert_eq!(resolve(&root, "dir/missing.txt"), None);
+    }
+
+    #[test]
+    fn rejects_dot_dot() {
+        let (_dir, root) = resolve_scratch();
+
+        assert_eq!(resolve(&root, "../secret.txt"), None);
+        assert_eq!(resolve(&root, "dir/../../secret.txt"), None);
+        assert_eq!(resolve(&root, "dir/../dir/file.txt"), None);
+        assert_eq!(resolve(&root, "./dir/file.txt"), None);
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        let (dir, root) = resolve_scratch();
+
+        let secret = dir.path().join("secret.txt");
+        assert_eq!(resolve(&root, &secret.to_string_lossy()), None);
+        let inside = root.join("dir/file.txt");
+        assert_eq!(resolve(&root, &inside.to_string_lossy()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_the_root() {
+        let (dir, root) = resolve_scratch();
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), root.join("escape")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("parent")).unwrap();
+        std::os::unix::fs::symlink(root.join("dir/file.txt"), root.join("alias")).unwrap();
+
+        assert_eq!(resolve(&root, "escape"), None);
+        assert_eq!(resolve(&root, "parent/secret.txt"), None);
+        let expected = root.join("dir/file.txt").canonicalize().unwrap();
+        assert_eq!(resolve(&root, "alias"), Some(expected));
+    }
+}
@@ -1,20 +1,54 @@
-use crate::config::McpServerConfig;
+pub mod backend;
+mod budget;
+mod files;
+mod permissions;
+mod retention;
+mod security;
+mod worktree;
+
+pub use budget::BudgetExceeded;
+pub use security::PolicyViolation;
+pub use worktree::MergeConflict;
+
+use crate::config::{
+    AgentsConfig, BackendConfig, McpServerConfig, ProfileConfig, RetentionConfig,
+};
 use crate::db::Database;
-use crate::models::{AgentSession, AgentStatus};
+use crate::models::{
+    AgentMessage, AgentSession, AgentStatus, Notification, PermissionDecision, PermissionMode,
+    PermissionRequest, ResolvePermission, SessionFile, Task, TaskComment, TaskStatus, TimeoutMode,
+    UpdateTask,
+};
 use anyhow::Result;
-use std::collections::HashMap;
-use std::process::Stdio;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
-use tokio::sync::{broadcast, oneshot};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_util::task::TaskTracker;
 
-/// Max time to wait for the first output line (covers MCP server startup).
-const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+use backend::{AgentBackend, AgentRun, BackendEvent, Resume, RunRequest};
+use budget::Budget;
+use permissions::Permissions;
+use security::SecurityPolicy;
+use worktree::Worktree;
 
-/// Max total time for a Claude subprocess after startup.
-const SESSION_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Name of Porter's own MCP server in session MCP configs.
+const PORTER_MCP: &str = "porter";
+
+/// The `porter mcp` tool Claude calls for permission decisions.
+const PERMISSION_PROMPT_TOOL: &str = "mcp__porter__approve_permission";
+
+/// Backend for sessions that don't name one: the configured Claude CLI.
+const DEFAULT_BACKEND: &str = "claude";
+
+/// How often a run whose timeout is paused for a permission request checks
+/// whether the request has been answered.
+const PERMISSION_RECHECK: Duration = Duration::from_secs(1);
+
+/// Follow-up sent to sessions resumed after a server restart.
+const ORPHAN_RESUME_PROMPT: &str =
+    "Your previous run was interrupted because the Porter server stopped. Continue where you left off.";
 
 /// Events emitted by an agent session.
 #[derive(Debug, Clone)]
@@ -28,6 +62,18 @@ pub enum AgentEvent {
         session_id: String,
         status: AgentStatus,
     },
+    /// A notification raised by the agent system (e.g. budget warnings).
+    /// Already persisted when sent.
+    Notification(Notification),
+    /// A task linked to a session changed status.
+    TaskUpdated(Task),
+    /// Claude is waiting for the user to allow or deny a tool call.
+    PermissionRequested(PermissionRequest),
+    PermissionResolved {
+        session_id: String,
+        request_id: String,
+        allow: bool,
+    },
 }
 
 /// Options for starting a new agent session.
@@ -35,39 +81,191 @@ pub enum AgentEvent {
 pub struct SessionOptions {
     pub working_directory: Option<String>,
     pub dangerously_skip_permissions: bool,
+    /// Schedule this session was started by.
+    pub schedule_id: Option<String>,
+    /// Task this session works on; see [`AgentManager::start_task_session`].
+    pub task_id: Option<String>,
+    /// Mark the linked task completed when a run succeeds.
+    pub complete_task: bool,
+    /// Model to use instead of `default_model`; must be in `allowed_models`.
+    pub model: Option<String>,
+    /// Extra instructions appended to Claude's system prompt.
+    pub append_system_prompt: Option<String>,
+    pub allowed_tools: Vec<String>,
+    pub disallowed_tools: Vec<String>,
+    pub max_turns: Option<u32>,
+    /// Names of configured MCP servers to give the session; `None` means all.
+    pub mcp_servers: Option<Vec<String>>,
+    /// `[agents.profiles]` entry supplying defaults for unset options.
+    pub profile: Option<String>,
+    pub permission_mode: Option<PermissionMode>,
+    /// Seconds a run may take before it is killed.
+    pub timeout_secs: Option<u64>,
+    /// Seconds to wait for a run's first output.
+    pub startup_timeout_secs: Option<u64>,
+    pub timeout_mode: Option<TimeoutMode>,
+    /// Work in a new git worktree and branch of `working_directory`'s
+    /// repository rather than in the directory itself.
+    pub worktree: bool,
+    /// `[agents.backends]` entry to run on instead of Claude.
+    pub backend: Option<String>,
+}
+
+/// Returned (via `anyhow`) when [`SessionOptions`] ask for something the
+/// config doesn't allow, such as an unknown model or MCP server.
+#[derive(Debug)]
+pub struct InvalidSessionOptions(pub String);
+
+impl std::fmt::Display for InvalidSessionOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
-/// Manages Claude agent subprocess sessions.
+impl std::error::Error for InvalidSessionOptions {}
+
+/// Returned (via `anyhow`) when a session can't be messaged, forked or
+/// deleted because it is running or queued.
+#[derive(Debug)]
+pub struct SessionBusy;
+
+impl std::fmt::Display for SessionBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Session is still running")
+    }
+}
+
+impl std::error::Error for SessionBusy {}
+
+/// Manages agent sessions, run by the Claude CLI or another [`AgentBackend`].
+///
+/// Cloning is cheap and shares all state; spawned runs hold a clone so they
+/// can start the next queued session when they finish.
+#[derive(Clone)]
 pub struct AgentManager {
     db: Database,
-    claude_binary: String,
+    /// By name; sessions without a backend use `"claude"`.
+    backends: HashMap<String, Arc<dyn AgentBackend>>,
     max_concurrent: usize,
     default_model: String,
+    allowed_models: Vec<String>,
     mcp_servers: HashMap<String, McpServerConfig>,
+    profiles: HashMap<String, ProfileConfig>,
+    /// Config defaults; sessions may override each part.
+    timeouts: RunTimeouts,
+    resume_orphaned: bool,
+    retention: RetentionConfig,
+    budget: Budget,
+    permissions: Permissions,
+    security: SecurityPolicy,
+    /// Route Claude's permission prompts through Porter's MCP server.
+    permission_prompts: bool,
     event_tx: broadcast::Sender<AgentEvent>,
     cancel_senders: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
+    /// Serialises slot accounting between starting, queueing and dispatching.
+    dispatch_lock: Arc<tokio::sync::Mutex<()>>,
+    /// Every spawned run, so shutdown can wait for them.
+    tasks: TaskTracker,
+    /// Set once shutdown starts; queued sessions are no longer started.
+    draining: Arc<AtomicBool>,
+    /// Sessions killed by shutdown, to be marked `Paused` rather than failed.
+    pausing: Arc<Mutex<HashSet<String>>>,
+    /// Sessions cancelled for going over their budget, with the reason.
+    over_budget: Arc<Mutex<HashMap<String, String>>>,
+    /// How long a persistent run may idle; `None` starts one
+    /// run per message instead.
+    persistent_idle: Option<Duration>,
+    /// Follow-up senders for sessions with a live run.
+    live_inputs: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<String>>>>,
 }
 
 impl AgentManager {
+    /// Create a manager. `porter_mcp` is Porter's own MCP server (`porter mcp`);
+    /// when given it is injected into every session alongside the configured
+    /// servers, unless the config already defines a server named "porter".
+    /// It also answers Claude's permission prompts when `permission_prompts`
+    /// is on.
     pub fn new(
         db: Database,
-        claude_binary: String,
-        max_concurrent: usize,
-        default_model: String,
-        mcp_servers: HashMap<String, McpServerConfig>,
+        config: &AgentsConfig,
+        porter_mcp: Option<McpServerConfig>,
     ) -> Self {
+        let mut mcp_servers = config.mcp.clone();
+        let mut porter_injected = false;
+        if let Some(porter) = porter_mcp {
+            if !mcp_servers.contains_key(PORTER_MCP) {
+                mcp_servers.insert(PORTER_MCP.to_string(), porter);
+                porter_injected = true;
+            }
+        }
+
+        let mut backends: HashMap<String, Arc<dyn AgentBackend>> = HashMap::new();
+        backends.insert(
+            DEFAULT_BACKEND.to_string(),
+            Arc::new(backend::ClaudeCli::new(&config.claude_binary)),
+        );
+        for (name, backend) in &config.backends {
+            let backend: Arc<dyn AgentBackend> = match backend {
+                BackendConfig::ClaudeCli { binary } => Arc::new(backend::ClaudeCli::new(binary)),
+                BackendConfig::Openai {
+                    base_url,
+                    api_key,
+                    model,
+                } => Arc::new(backend::OpenAiCompatible::new(
+                    db.clone(),
+                    base_url,
+                    api_key.clone(),
+                    model.clone(),
+                )),
+            };
+            backends.insert(name.clone(), backend);
+        }
+
         let (event_tx, _) = broadcast::channel(256);
         Self {
+            budget: Budget::new(config.budget.clone(), db.clone(), event_tx.clone()),
+            permissions: Permissions::new(
+                db.clone(),
+                event_tx.clone(),
+                Duration::from_secs(config.permission_timeout_secs),
+            ),
+            permission_prompts: config.permission_prompts && porter_injected,
+            security: SecurityPolicy::new(&config.security),
             db,
-            claude_binary,
-            max_concurrent,
-            default_model,
+            backends,
+            max_concurrent: config.max_concurrent_sessions,
+            default_model: config.default_model.clone(),
+            allowed_models: config.allowed_models.clone(),
             mcp_servers,
+            profiles: config.profiles.clone(),
+            timeouts: RunTimeouts {
+                startup: Duration::from_secs(config.startup_timeout_secs),
+                limit: Duration::from_secs(config.session_timeout_secs),
+                mode: config.timeout_mode,
+            },
+            resume_orphaned: config.resume_orphaned_sessions,
+            retention: config.retention.clone(),
             event_tx,
             cancel_senders: Arc::new(Mutex::new(HashMap::new())),
+            dispatch_lock: Arc::new(tokio::sync::Mutex::new(())),
+            tasks: TaskTracker::new(),
+            draining: Arc::new(AtomicBool::new(false)),
+            pausing: Arc::new(Mutex::new(HashSet::new())),
+            over_budget: Arc::new(Mutex::new(HashMap::new())),
+            persistent_idle: config
+                .persistent_sessions
+                .then(|| Duration::from_secs(config.persistent_idle_secs)),
+            live_inputs: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Make `backend` available to sessions as `name`, replacing any
+    /// configured backend of that name.
+    pub fn with_backend(mut self, name: &str, backend: Arc<dyn AgentBackend>) -> Self {
+        self.backends.insert(name.to_string(), backend);
+        self
+    }
+
     /// Subscribe to agent events (for WebSocket broadcasting).
     pub fn subscribe(&self) -> broadcast::Receiver<AgentEvent> {
         self.event_tx.subscribe()
@@ -78,97 +276,726 @@ impl AgentManager {
         self.mcp_servers.keys().cloned().collect()
     }
 
-    /// Start a new Claude agent session.
+    /// Start a new Claude agent session, or queue it if `max_concurrent`
+    /// sessions are already running (or others are already waiting).
     pub async fn start_session(
         &self,
         prompt: &str,
         opts: SessionOptions,
     ) -> Result<AgentSession> {
-        let running = self.db.list_agent_sessions(Some("running")).await?;
-        if running.len() >= self.max_concurrent {
-            anyhow::bail!(
-                "Maximum concurrent sessions ({}) reached",
-                self.max_concurrent
+        let mut opts = self.apply_profile(opts)?;
+        self.validate_options(&opts)?;
+        self.security
+            .check_permissions(opts.dangerously_skip_permissions, opts.permission_mode)?;
+        if let Some(dir) = opts.working_directory.as_deref() {
+            let dir = self.security.check_directory(dir)?;
+            opts.working_directory = Some(dir.to_string_lossy().into_owned());
+        }
+        self.budget.check_global().await?;
+
+        let mut session = AgentSession {
+            working_directory: opts.working_directory,
+            dangerously_skip_permissions: opts.dangerously_skip_permissions,
+            schedule_id: opts.schedule_id,
+            task_id: opts.task_id,
+            complete_task: opts.complete_task,
+            append_system_prompt: opts.append_system_prompt,
+            allowed_tools: opts.allowed_tools,
+            disallowed_tools: opts.disallowed_tools,
+            max_turns: opts.max_turns,
+            mcp_servers: opts.mcp_servers,
+            profile: opts.profile,
+            permission_mode: opts.permission_mode,
+            timeout_secs: opts.timeout_secs,
+            startup_timeout_secs: opts.startup_timeout_secs,
+            timeout_mode: opts.timeout_mode,
+            backend: opts.backend,
+            ..AgentSession::new(prompt, opts.model.as_deref().unwrap_or(&self.default_model))
+        };
+        let mut worktree = None;
+        if opts.worktree {
+            let dir = session.working_directory.as_deref().unwrap_or_default();
+            // The repository is what resumes are checked against, so check
+            // it before adding a worktree to it
+            let repo = Worktree::repo_root(dir).await?;
+            let repo = self.security.check_directory(&repo.to_string_lossy())?;
+            let path = porter_dir("worktrees")?.join(&session.id);
+            let created = Worktree::create(repo, path, &session.id).await?;
+            tracing::info!(session_id = %session.id, branch = %created.branch, "Created worktree");
+            session.working_directory = Some(created.path.to_string_lossy().into_owned());
+            session.worktree_repo = Some(created.repo.to_string_lossy().into_owned());
+            session.worktree_branch = Some(created.branch.clone());
+            worktree = Some(created);
+        }
+
+        let _guard = self.dispatch_lock.lock().await;
+        let inserted = async {
+            let running = self.db.list_agent_sessions(Some("running")).await?;
+            let queue_len = self.db.list_queued_sessions().await?.len();
+            session.status = if running.len() >= self.max_concurrent || queue_len > 0 {
+                AgentStatus::Queued
+            } else {
+                AgentStatus::Running
+            };
+            self.db.create_agent_session(session).await
+        }
+        .await;
+        let session = match inserted {
+            Ok(session) => session,
+            Err(e) => {
+                // Don't leave a branch behind for a session that doesn't exist
+                if let Some(worktree) = worktree {
+                    if let Err(e) = worktree.remove().await {
+                        tracing::warn!(path = %worktree.path.display(), error = %e, "Failed to remove worktree");
+                    }
+                }
+                return Err(e);
+            }
+        };
+
+        if session.status == AgentStatus::Queued {
+            tracing::info!(
+                session_id = %session.id,
+                position = ?session.queue_position,
+                "Agent session queued"
             );
+        } else {
+            self.launch(&session);
+        }
+
+        Ok(session)
+    }
+
+    /// Fill options the caller left unset from the named profile, if any.
+    fn apply_profile(&self, opts: SessionOptions) -> Result<SessionOptions> {
+        let Some(name) = opts.profile.as_deref() else {
+            return Ok(opts);
+        };
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| InvalidSessionOptions(format!("Unknown profile '{name}'")))?
+            .clone();
+
+        Ok(SessionOptions {
+            working_directory: opts.working_directory.or(profile.directory),
+            dangerously_skip_permissions: opts.dangerously_skip_permissions
+                || profile.dangerously_skip_permissions,
+            model: opts.model.or(profile.model),
+            append_system_prompt: opts.append_system_prompt.or(profile.append_system_prompt),
+            allowed_tools: if opts.allowed_tools.is_empty() {
+                profile.allowed_tools
+            } else {
+                opts.allowed_tools
+            },
+            disallowed_tools: if opts.disallowed_tools.is_empty() {
+                profile.disallowed_tools
+            } else {
+                opts.disallowed_tools
+            },
+            max_turns: opts.max_turns.or(profile.max_turns),
+            mcp_servers: opts.mcp_servers.or(profile.mcp_servers),
+            permission_mode: opts.permission_mode.or(profile.permission_mode),
+            timeout_secs: opts.timeout_secs.or(profile.timeout_secs),
+            startup_timeout_secs: opts.startup_timeout_secs.or(profile.startup_timeout_secs),
+            timeout_mode: opts.timeout_mode.or(profile.timeout_mode),
+            worktree: opts.worktree || profile.worktree,
+            backend: opts.backend.or(profile.backend),
+            ..opts
+        })
+    }
+
+    /// Names of the configured `[agents.profiles]`.
+    pub fn profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn validate_options(&self, opts: &SessionOptions) -> Result<()> {
+        let invalid = |msg: String| Err(InvalidSessionOptions(msg).into());
+
+        if let Some(model) = opts.model.as_deref() {
+            if model.trim().is_empty() {
+                return invalid("Model must not be empty".to_string());
+            }
+            if model != self.default_model
+                && !self.allowed_models.is_empty()
+                && !self.allowed_models.iter().any(|m| m == model)
+            {
+                return invalid(format!("Model '{model}' is not in agents.allowed_models"));
+            }
+        }
+        for name in opts.mcp_servers.iter().flatten() {
+            if !self.mcp_servers.contains_key(name) {
+                return invalid(format!("Unknown MCP server '{name}'"));
+            }
+        }
+        if opts
+            .allowed_tools
+            .iter()
+            .chain(&opts.disallowed_tools)
+            .any(|t| t.trim().is_empty())
+        {
+            return invalid("Tool names must not be empty".to_string());
+        }
+        if opts.max_turns == Some(0) {
+            return invalid("max_turns must be at least 1".to_string());
+        }
+        if opts.timeout_secs == Some(0) || opts.startup_timeout_secs == Some(0) {
+            return invalid("Timeouts must be at least 1 second".to_string());
+        }
+        if opts.worktree && opts.working_directory.is_none() {
+            return invalid("A worktree needs a directory in a git repository".to_string());
+        }
+        if let Some(name) = opts.backend.as_deref() {
+            if !self.backends.contains_key(name) {
+                return invalid(format!("Unknown backend '{name}'"));
+            }
         }
+        Ok(())
+    }
 
+    /// The timeouts for `session`'s runs: its overrides, else the config's.
+    fn session_timeouts(&self, session: &AgentSession) -> RunTimeouts {
+        RunTimeouts {
+            startup: session
+                .startup_timeout_secs
+                .map_or(self.timeouts.startup, Duration::from_secs),
+            limit: session
+                .timeout_secs
+                .map_or(self.timeouts.limit, Duration::from_secs),
+            mode: session.timeout_mode.unwrap_or(self.timeouts.mode),
+        }
+    }
+
+    /// The configured MCP servers `session` has access to.
+    fn session_mcp_servers(&self, session: &AgentSession) -> HashMap<String, McpServerConfig> {
+        let mut servers: HashMap<String, McpServerConfig> = match &session.mcp_servers {
+            Some(names) => self
+                .mcp_servers
+                .iter()
+                .filter(|(name, _)| names.contains(name))
+                .map(|(name, server)| (name.clone(), server.clone()))
+                .collect(),
+            None => self.mcp_servers.clone(),
+        };
+
+        // Porter's server needs to know which session it is answering for
+        if self.uses_permission_prompts(session) {
+            if let Some(porter) = servers.get_mut(PORTER_MCP) {
+                porter
+                    .env
+                    .insert("PORTER_SESSION_ID".to_string(), session.id.clone());
+            }
+        }
+        servers
+    }
+
+    /// Whether Claude should ask Porter before using tools in `session`.
+    /// Unattended sessions (scheduled or working on a task) never ask, as
+    /// nobody would answer.
+    fn uses_permission_prompts(&self, session: &AgentSession) -> bool {
+        self.permission_prompts
+            && session.schedule_id.is_none()
+            && session.task_id.is_none()
+            && !session.dangerously_skip_permissions
+            && session.permission_mode != Some(PermissionMode::BypassPermissions)
+            && session
+                .mcp_servers
+                .as_ref()
+                .is_none_or(|names| names.iter().any(|n| n == PORTER_MCP))
+    }
+
+    /// Handle a permission prompt from a session's Claude run: allowed by
+    /// rule, or held until the user answers (see [`Self::resolve_permission`]).
+    pub async fn request_permission(
+        &self,
+        session_id: &str,
+        tool_name: &str,
+        input: serde_json::Value,
+        tool_use_id: Option<&str>,
+    ) -> Result<PermissionDecision> {
         let session = self
             .db
-            .create_agent_session(
-                prompt,
-                &self.default_model,
-                opts.working_directory.as_deref(),
-                opts.dangerously_skip_permissions,
+            .get_agent_session(session_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        self.permissions
+            .request(&session, tool_name, input, tool_use_id)
+            .await
+    }
+
+    /// Deny all permission requests, pending or to come, so none holds up
+    /// shutdown. Called as soon as shutdown starts; [`Self::shutdown`] does
+    /// it too.
+    pub fn deny_permission_requests(&self) {
+        self.permissions.close();
+    }
+
+    /// Permission requests from `session_id` waiting for an answer.
+    pub fn pending_permissions(&self, session_id: &str) -> Vec<PermissionRequest> {
+        self.permissions.pending(session_id)
+    }
+
+    /// Answer a pending permission request, optionally remembering an
+    /// allow as a rule. Returns false if no such request is pending.
+    pub async fn resolve_permission(
+        &self,
+        session_id: &str,
+        request_id: &str,
+        input: ResolvePermission,
+    ) -> Result<bool> {
+        let Some(session) = self.db.get_agent_session(session_id).await? else {
+            return Ok(false);
+        };
+        self.permissions.resolve(&session, request_id, input).await
+    }
+
+    /// Start a session working on `task`. The prompt carries the task's
+    /// details plus optional extra `instructions`, and the task is moved to
+    /// `InProgress`. When a run finishes the agent's reply (or error) is added
+    /// as a task comment and, if `opts.complete_task` is set, a successful run
+    /// marks the task completed.
+    pub async fn start_task_session(
+        &self,
+        task: &Task,
+        instructions: Option<&str>,
+        opts: SessionOptions,
+    ) -> Result<AgentSession> {
+        let prompt = task_prompt(task, instructions);
+        let session = self
+            .start_session(
+                &prompt,
+                SessionOptions {
+                    task_id: Some(task.id.clone()),
+                    ..opts
+                },
             )
             .await?;
 
-        let session_id = session.id.clone();
-        let prompt = prompt.to_string();
-        let claude_binary = self.claude_binary.clone();
-        let mcp_servers = self.mcp_servers.clone();
-        let db = self.db.clone();
-        let event_tx = self.event_tx.clone();
-        let working_directory = session.working_directory.clone();
-        let skip_permissions = session.dangerously_skip_permissions;
+        if task.status != TaskStatus::InProgress {
+            self.set_task_status(&task.id, TaskStatus::InProgress).await?;
+        }
+        Ok(session)
+    }
+
+    /// Start queued sessions, in queue order, while slots are free. Called
+    /// whenever a run finishes and once at startup.
+    pub async fn dispatch_queue(&self) -> Result<()> {
+        let _guard = self.dispatch_lock.lock().await;
+        if self.draining.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let running = self.db.list_agent_sessions(Some("running")).await?.len();
+        let free = self.max_concurrent.saturating_sub(running);
+        if free == 0 {
+            return Ok(());
+        }
+
+        for queued in self.db.list_queued_sessions().await?.into_iter().take(free) {
+            if let Err(e) = self.budget.check_global().await {
+                tracing::warn!(error = %e, "Leaving agent sessions queued");
+                break;
+            }
+            let Some(session) = self.db.start_queued_session(&queued.id).await? else {
+                continue;
+            };
+            let _ = self.event_tx.send(AgentEvent::StatusChanged {
+                session_id: session.id.clone(),
+                status: AgentStatus::Running,
+            });
+            self.launch(&session);
+        }
+
+        Ok(())
+    }
+
+    /// Reconcile sessions still marked `running` from a previous server
+    /// process. No subprocess survives a restart, so each is either resumed
+    /// (when `resume_orphaned_sessions` is set and it has a Claude session to
+    /// resume) or marked failed with an explanatory error message.
+    /// Must be called before any session is started.
+    pub async fn recover_orphaned_sessions(&self) -> Result<()> {
+        let orphans = self.db.list_agent_sessions(Some("running")).await?;
+
+        for session in orphans {
+            if self.resume_orphaned && session.claude_session_id.is_some() {
+                self.db
+                    .add_agent_message(
+                        &session.id,
+                        "system",
+                        "Porter restarted while this session was running; resuming.",
+                    )
+                    .await?;
+                match self.continue_session(session.clone(), ORPHAN_RESUME_PROMPT).await {
+                    Ok(()) => {
+                        tracing::info!(session_id = %session.id, "Resumed orphaned agent session");
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::warn!(session_id = %session.id, error = %e, "Could not resume orphaned agent session");
+                    }
+                }
+            }
+
+            self.db
+                .add_agent_message(
+                    &session.id,
+                    "error",
+                    "Porter stopped while this session was running, so it was marked failed on restart.",
+                )
+                .await?;
+            self.db
+                .update_agent_session_status(&session.id, AgentStatus::Failed)
+                .await?;
+            let _ = self.event_tx.send(AgentEvent::StatusChanged {
+                session_id: session.id.clone(),
+                status: AgentStatus::Failed,
+            });
+            tracing::warn!(session_id = %session.id, "Marked orphaned agent session failed");
+        }
+
+        Ok(())
+    }
+
+    /// Stop for server shutdown. Queued sessions stay queued for the next
+    /// start; running ones get `grace` to finish, after which their
+    /// subprocesses are killed and the sessions marked `Paused` so they can
+    /// be resumed with a follow-up message.
+    pub async fn shutdown(&self, grace: Duration) {
+        self.draining.store(true, Ordering::SeqCst);
+        self.permissions.close();
+        self.tasks.close();
+        // Live processes exit once their input closes (after any current turn)
+        self.live_inputs.lock().unwrap().clear();
+
+        if tokio::time::timeout(grace, self.tasks.wait()).await.is_ok() {
+            return;
+        }
+
+        let senders: Vec<_> = self.cancel_senders.lock().unwrap().drain().collect();
+        tracing::warn!(
+            sessions = senders.len(),
+            "Shutdown grace period elapsed, pausing running agent sessions"
+        );
+        for (session_id, tx) in senders {
+            self.pausing.lock().unwrap().insert(session_id);
+            let _ = tx.send(());
+        }
+        self.tasks.wait().await;
+    }
+
+    /// A live process answered a message and is waiting for the next one.
+    async fn finish_turn(&self, session_id: &str) {
+        let _ = self
+            .db
+            .update_agent_session_status(session_id, AgentStatus::Completed)
+            .await;
+        let _ = self.event_tx.send(AgentEvent::StatusChanged {
+            session_id: session_id.to_string(),
+            status: AgentStatus::Completed,
+        });
+
+        self.snapshot_files(session_id).await;
+        if let Err(e) = self.report_to_task(session_id, AgentStatus::Completed).await {
+            tracing::error!(session_id = %session_id, error = %e, "Failed to update linked task");
+        }
+        if let Err(e) = self.dispatch_queue().await {
+            tracing::error!(error = %e, "Failed to dispatch queued agent sessions");
+        }
+    }
+
+    /// Record how a run ended and hand its slot to the next queued session.
+    async fn finish_run(&self, session_id: &str, result: Result<()>) {
+        // A message may already have started the next run; its channels
+        // are still open and stay registered
+        remove_ended(&self.cancel_senders, session_id, oneshot::Sender::is_closed);
+        self.permissions.clear_session(session_id);
+
+        // A live process stopped between turns (idle, cancelled, shutdown)
+        // leaves the session as its last turn did
+        let was_live = remove_ended(&self.live_inputs, session_id, mpsc::UnboundedSender::is_closed);
+        let session = self.db.get_agent_session(session_id).await;
+        // Stopped by deleting the session: nothing left to record
+        if matches!(session, Ok(None)) {
+            self.pausing.lock().unwrap().remove(session_id);
+            tracing::debug!(session_id = %session_id, "Agent session deleted before its run ended");
+            return;
+        }
+        if was_live {
+            let running = match session {
+                Ok(Some(session)) => session.status == AgentStatus::Running,
+                Ok(None) => false,
+                Err(_) => true,
+            };
+            if !running {
+                self.pausing.lock().unwrap().remove(session_id);
+                if let Err(e) = result {
+                    tracing::warn!(session_id = %session_id, error = %e, "Idle agent run ended with an error");
+                }
+                return;
+            }
+        }
+        let paused = self.pausing.lock().unwrap().remove(session_id);
+
+        let final_status = match result {
+            Ok(()) => AgentStatus::Completed,
+            Err(_) if paused => {
+                tracing::info!(session_id = %session_id, "Agent session paused by shutdown");
+                let _ = self
+                    .db
+                    .add_agent_message(
+                        session_id,
+                        "system",
+                        "Paused because the Porter server shut down. Send a message to resume.",
+                    )
+                    .await;
+                AgentStatus::Paused
+            }
+            Err(e) => {
+                tracing::error!(session_id = %session_id, error = %e, "Agent session failed");
+                let _ = self
+                    .db
+                    .add_agent_message(session_id, "error", &e.to_string())
+                    .await;
+                AgentStatus::Failed
+            }
+        };
+
+        let _ = self
+            .db
+            .update_agent_session_status(session_id, final_status)
+            .await;
+        let _ = self.event_tx.send(AgentEvent::StatusChanged {
+            session_id: session_id.to_string(),
+            status: final_status,
+        });
+
+        if final_status == AgentStatus::Completed {
+            self.snapshot_files(session_id).await;
+        }
+        if let Err(e) = self.report_to_task(session_id, final_status).await {
+            tracing::error!(session_id = %session_id, error = %e, "Failed to update linked task");
+        }
+
+        if let Err(e) = self.dispatch_queue().await {
+            tracing::error!(error = %e, "Failed to dispatch queued agent sessions");
+        }
+    }
+
+    /// Copy the files a completed turn changed in the session's working
+    /// directory into a new snapshot under `~/.porter/snapshots/{id}`.
+    async fn snapshot_files(&self, session_id: &str) {
+        let result = async {
+            let Some(session) = self.db.get_agent_session(session_id).await? else {
+                return Ok(None);
+            };
+            let Some(root) = session.working_directory.map(std::path::PathBuf::from) else {
+                return Ok(None);
+            };
+            let dir = porter_dir("snapshots")?.join(session_id);
+            tokio::task::spawn_blocking(move || files::snapshot(&root, &dir, session.started_at))
+                .await?
+        }
+        .await;
+
+        match result {
+            Ok(Some((name, count))) => {
+                tracing::info!(session_id = %session_id, snapshot = %name, files = count, "Saved snapshot");
+                let _ = self
+                    .db
+                    .add_agent_message(
+                        session_id,
+                        "system",
+                        &format!("Saved {count} changed file(s) to snapshot {name}."),
+                    )
+                    .await;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!(session_id = %session_id, error = %e, "Failed to snapshot session files");
+            }
+        }
+    }
+
+    /// Comment on the session's linked task (if any) with how the run ended,
+    /// and update the task's status.
+    async fn report_to_task(&self, session_id: &str, status: AgentStatus) -> Result<()> {
+        let Some(session) = self.db.get_agent_session(session_id).await? else {
+            return Ok(());
+        };
+        let Some(task_id) = session.task_id.as_deref() else {
+            return Ok(());
+        };
+        if self.db.get_task(task_id).await?.is_none() {
+            return Ok(());
+        }
+
+        let messages = self.db.get_agent_messages(session_id).await?;
+        let (content, new_status) = match status {
+            AgentStatus::Completed => {
+                let reply = messages
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == "assistant" && m.content_type == "text")
+                    .map(|m| m.content.as_str())
+                    .unwrap_or("Agent session finished without a reply.");
+                let new_status = session.complete_task.then_some(TaskStatus::Completed);
+                (reply.to_string(), new_status)
+            }
+            AgentStatus::Failed => {
+                let error = messages
+                    .iter()
+                    .rev()
+                    .find(|m| m.role == "error")
+                    .map(|m| m.content.as_str())
+                    .unwrap_or("unknown error");
+                (format!("Agent session failed: {error}"), Some(TaskStatus::Pending))
+            }
+            _ => return Ok(()),
+        };
+
+        self.db
+            .add_task_comment(&TaskComment::new(task_id, "agent", &content, Some(session_id)))
+            .await?;
+        if let Some(new_status) = new_status {
+            self.set_task_status(task_id, new_status).await?;
+        }
+        Ok(())
+    }
+
+    async fn set_task_status(&self, task_id: &str, status: TaskStatus) -> Result<()> {
+        let update = UpdateTask {
+            title: None,
+            description: None,
+            status: Some(status),
+            priority: None,
+            tags: None,
+            due_date: None,
+        };
+        if let Some(task) = self.db.update_task(task_id, update).await? {
+            let _ = self.event_tx.send(AgentEvent::TaskUpdated(task));
+        }
+        Ok(())
+    }
+
+    /// Sessions waiting for a free slot, in the order they will start.
+    pub async fn list_queue(&self) -> Result<Vec<AgentSession>> {
+        self.db.list_queued_sessions().await
+    }
+
+    /// Move a queued session to `position` (1-based). Returns false if the
+    /// session isn't queued.
+    pub async fn move_in_queue(&self, id: &str, position: usize) -> Result<bool> {
+        let _guard = self.dispatch_lock.lock().await;
+        self.db.move_queued_session(id, position).await
+    }
+
+    /// Spawn the initial run for a session already marked `Running`.
+    fn launch(&self, session: &AgentSession) {
+        self.spawn_run(session.clone(), session.prompt.clone(), None);
+    }
+
+    /// Start a run of `session` on its backend, sending `prompt` (continuing
+    /// the conversation `resume` if given). With persistent sessions the run
+    /// stays up for follow-ups until it has been idle too long.
+    fn spawn_run(&self, session: AgentSession, prompt: String, resume: Option<Resume>) {
+        let spec = RunSpec {
+            backend: self
+                .backends
+                .get(session.backend.as_deref().unwrap_or(DEFAULT_BACKEND))
+                .cloned(),
+            mcp_servers: self.session_mcp_servers(&session),
+            permission_tool: self
+                .uses_permission_prompts(&session)
+                .then_some(PERMISSION_PROMPT_TOOL),
+            timeouts: self.session_timeouts(&session),
+            security: self.security.clone(),
+            session,
+            prompt,
+            resume,
+        };
+        let session_id = spec.session.id.clone();
 
         let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
         self.cancel_senders
             .lock()
             .unwrap()
             .insert(session_id.clone(), cancel_tx);
-        let cancel_senders = self.cancel_senders.clone();
 
-        tokio::spawn(async move {
-            let result = run_claude_session(
-                &claude_binary,
-                &prompt,
-                &session_id,
-                &mcp_servers,
-                working_directory.as_deref(),
-                skip_permissions,
-                &db,
-                &event_tx,
-                cancel_rx,
-            )
-            .await;
-
-            cancel_senders.lock().unwrap().remove(&session_id);
+        let (live, turns) = match self.persistent_idle {
+            Some(idle) => {
+                let (message_tx, messages) = mpsc::unbounded_channel();
+                let (turn_done, turns) = mpsc::unbounded_channel();
+                self.live_inputs
+                    .lock()
+                    .unwrap()
+                    .insert(session_id.clone(), message_tx);
+                (
+                    Some(LiveInput {
+                        messages,
+                        turn_done,
+                        idle,
+                    }),
+                    Some(turns),
+                )
+            }
+            None => (None, None),
+        };
 
-            let final_status = match result {
-                Ok(()) => AgentStatus::Completed,
-                Err(e) => {
-                    tracing::error!(session_id = %session_id, error = %e, "Agent session failed");
-                    let _ = db
-                        .add_agent_message(&session_id, "error", &e.to_string())
-                        .await;
-                    AgentStatus::Failed
+        let manager = self.clone();
+        self.tasks.spawn(async move {
+            let run = run_agent(spec, live, &manager, cancel_rx);
+            let result = match turns {
+                Some(mut turns) => {
+                    tokio::pin!(run);
+                    loop {
+                        tokio::select! {
+                            result = &mut run => break result,
+                            Some(()) = turns.recv() => manager.finish_turn(&session_id).await,
+                        }
+                    }
                 }
+                None => run.await,
             };
 
-            let _ = db
-                .update_agent_session_status(&session_id, final_status)
-                .await;
-            let _ = event_tx.send(AgentEvent::StatusChanged {
-                session_id,
-                status: final_status,
-            });
+            manager.finish_run(&session_id, result).await;
         });
-
-        Ok(session)
     }
 
-    /// Send a follow-up message to an existing session.
+    /// Send a follow-up message to an existing session: written to its live
+    /// run if it has one, otherwise sent by resuming the conversation.
     pub async fn send_message(&self, session_id: &str, content: &str) -> Result<()> {
+        // Held until the session is marked running, so two messages can't
+        // both start a run
+        let _guard = self.dispatch_lock.lock().await;
         let session = self
             .db
             .get_agent_session(session_id)
             .await?
             .ok_or_else(|| anyhow::anyhow!("Session not found"))?;
+        if matches!(session.status, AgentStatus::Running | AgentStatus::Queued) {
+            return Err(SessionBusy.into());
+        }
+        self.continue_session(session, content).await
+    }
 
+    /// Send `content` to `session` regardless of its status.
+    async fn continue_session(&self, session: AgentSession, content: &str) -> Result<()> {
+        let session_id = session.id.as_str();
         let claude_session_id = session
             .claude_session_id
+            .clone()
             .ok_or_else(|| anyhow::anyhow!("Session has no claude_session_id"))?;
 
+        let live_tx = self.live_inputs.lock().unwrap().get(session_id).cloned();
+        let fork = self.is_unforked(&session).await?;
+
+        self.budget.check_global().await?;
+        self.budget.check_session(&session)?;
+
         // Store the user message
         self.db
             .add_agent_message(session_id, "user", content)
@@ -183,60 +1010,88 @@ impl AgentManager {
             status: AgentStatus::Running,
         });
 
-        let porter_session_id = session_id.to_string();
-        let content = content.to_string();
-        let claude_binary = self.claude_binary.clone();
-        let mcp_servers = self.mcp_servers.clone();
-        let db = self.db.clone();
-        let event_tx = self.event_tx.clone();
-        let working_directory = session.working_directory.clone();
-        let skip_permissions = session.dangerously_skip_permissions;
+        // The live process may have just exited; fall back to resuming
+        let content = match live_tx.map(|tx| tx.send(content.to_string())) {
+            Some(Ok(())) => return Ok(()),
+            Some(Err(mpsc::error::SendError(content))) => content,
+            None => content.to_string(),
+        };
+        let resume = Resume {
+            conversation_id: claude_session_id,
+            fork,
+        };
+        self.spawn_run(session, content, Some(resume));
 
-        let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
-        self.cancel_senders
-            .lock()
-            .unwrap()
-            .insert(porter_session_id.clone(), cancel_tx);
-        let cancel_senders = self.cancel_senders.clone();
-
-        tokio::spawn(async move {
-            let result = resume_claude_session(
-                &claude_binary,
-                &claude_session_id,
-                &content,
-                &porter_session_id,
-                &mcp_servers,
-                working_directory.as_deref(),
-                skip_permissions,
-                &db,
-                &event_tx,
-                cancel_rx,
-            )
-            .await;
+        Ok(())
+    }
 
-            cancel_senders.lock().unwrap().remove(&porter_session_id);
+    /// Start a new session from `id`'s conversation so far, with a copy of
+    /// its transcript. The fork shares the Claude session until its first
+    /// message, which branches it off with `--fork-session`, and shares the
+    /// working directory for good. Returns `None` if `id` doesn't exist.
+    pub async fn fork_session(&self, id: &str) -> Result<Option<AgentSession>> {
+        // Held so the parent can't be deleted meanwhile
+        let _guard = self.dispatch_lock.lock().await;
+        let Some(parent) = self.db.get_agent_session(id).await? else {
+            return Ok(None);
+        };
+        if parent.claude_session_id.is_none() {
+            return Err(
+                InvalidSessionOptions("Session has no conversation to fork yet".to_string()).into(),
+            );
+        }
+        if matches!(parent.status, AgentStatus::Running | AgentStatus::Queued) {
+            return Err(SessionBusy.into());
+        }
 
-            let final_status = match result {
-                Ok(()) => AgentStatus::Completed,
-                Err(e) => {
-                    tracing::error!(session_id = %porter_session_id, error = %e, "Agent resume failed");
-                    let _ = db
-                        .add_agent_message(&porter_session_id, "error", &e.to_string())
-                        .await;
-                    AgentStatus::Failed
-                }
-            };
+        // The fork is left idle, ready for a follow-up; it isn't linked to
+        // the parent's task or schedule
+        let fork = self
+            .db
+            .create_agent_session(AgentSession {
+                status: AgentStatus::Completed,
+                claude_session_id: parent.claude_session_id.clone(),
+                working_directory: parent.working_directory.clone(),
+                worktree_repo: parent.worktree_repo.clone(),
+                worktree_branch: parent.worktree_branch.clone(),
+                dangerously_skip_permissions: parent.dangerously_skip_permissions,
+                append_system_prompt: parent.append_system_prompt.clone(),
+                allowed_tools: parent.allowed_tools.clone(),
+                disallowed_tools: parent.disallowed_tools.clone(),
+                max_turns: parent.max_turns,
+                mcp_servers: parent.mcp_servers.clone(),
+                profile: parent.profile.clone(),
+                permission_mode: parent.permission_mode,
+                timeout_secs: parent.timeout_secs,
+                startup_timeout_secs: parent.startup_timeout_secs,
+                timeout_mode: parent.timeout_mode,
+                backend: parent.backend.clone(),
+                parent_session_id: Some(parent.id.clone()),
+                completed_at: Some(chrono::Utc::now()),
+                ..AgentSession::new(&parent.prompt, &parent.model)
+            })
+            .await?;
+        self.db.copy_agent_messages(&parent.id, &fork.id).await?;
 
-            let _ = db
-                .update_agent_session_status(&porter_session_id, final_status)
-                .await;
-            let _ = event_tx.send(AgentEvent::StatusChanged {
-                session_id: porter_session_id,
-                status: final_status,
-            });
+        tracing::info!(session_id = %fork.id, parent = %parent.id, "Forked agent session");
+        let _ = self.event_tx.send(AgentEvent::StatusChanged {
+            session_id: fork.id.clone(),
+            status: fork.status,
         });
+        Ok(Some(fork))
+    }
 
-        Ok(())
+    /// Whether `session` is a fork that hasn't branched off yet, i.e. still
+    /// shares its Claude session with its parent or sibling forks.
+    async fn is_unforked(&self, session: &AgentSession) -> Result<bool> {
+        match (&session.parent_session_id, &session.claude_session_id) {
+            (Some(_), Some(claude_session_id)) => {
+                self.db
+                    .claude_session_shared(&session.id, claude_session_id)
+                    .await
+            }
+            _ => Ok(false),
+        }
     }
 
     /// List all sessions, optionally filtered by status.
@@ -249,85 +1104,343 @@ impl AgentManager {
         self.db.get_agent_session(id).await
     }
 
-    /// Cancel a running session by killing its subprocess.
+    /// Cancel a session: kill its subprocess if running, or take it off the
+    /// queue if it hasn't started yet.
     pub async fn cancel_session(&self, id: &str) -> Result<bool> {
         let cancel_tx = self.cancel_senders.lock().unwrap().remove(id);
         if let Some(tx) = cancel_tx {
             let _ = tx.send(());
-            Ok(true)
-        } else {
-            Ok(false)
+            return Ok(true);
+        }
+
+        let _guard = self.dispatch_lock.lock().await;
+        let queued = self
+            .db
+            .get_agent_session(id)
+            .await?
+            .is_some_and(|s| s.status == AgentStatus::Queued);
+        if !queued {
+            return Ok(false);
+        }
+
+        self.db
+            .add_agent_message(id, "error", "Session was cancelled before it started")
+            .await?;
+        self.db
+            .update_agent_session_status(id, AgentStatus::Failed)
+            .await?;
+        let _ = self.event_tx.send(AgentEvent::StatusChanged {
+            session_id: id.to_string(),
+            status: AgentStatus::Failed,
+        });
+        Ok(true)
+    }
+
+    /// Cancel `session_id`'s run because `reason` put it over budget. Returns
+    /// false if the run is already ending.
+    fn cancel_over_budget(&self, session_id: &str, reason: &anyhow::Error) -> bool {
+        let Some(tx) = self.cancel_senders.lock().unwrap().remove(session_id) else {
+            return false;
+        };
+        self.over_budget
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), reason.to_string());
+        if tx.send(()).is_err() {
+            self.over_budget.lock().unwrap().remove(session_id);
+            return false;
         }
+        true
     }
 
-    /// Delete a session and its messages.
+    /// Delete a session with its messages, permission rules, files and
+    /// worktree. Running and queued sessions can't be deleted (cancel them
+    /// first); a persistent process idling for it is stopped.
     pub async fn delete_session(&self, id: &str) -> Result<bool> {
-        self.db.delete_agent_session(id).await
+        let sessions_dir = porter_dir("sessions")?;
+        let (session, shared) = {
+            // Held so the session can't be started, messaged or forked
+            // meanwhile
+            let _guard = self.dispatch_lock.lock().await;
+            let Some(session) = self.db.get_agent_session(id).await? else {
+                return Ok(false);
+            };
+            if matches!(session.status, AgentStatus::Running | AgentStatus::Queued) {
+                return Err(SessionBusy.into());
+            }
+            self.live_inputs.lock().unwrap().remove(id);
+            if !self.db.delete_agent_session(id).await? {
+                return Ok(false);
+            }
+            // Forks work in their parent's directory, so it stays while any
+            // of them is left
+            let shared = match session.working_directory.as_deref() {
+                Some(dir) => self.db.working_directory_shared(id, dir).await?,
+                None => false,
+            };
+            (session, shared)
+        };
+
+        if let Some(worktree) = Worktree::of(&session).filter(|_| !shared) {
+            if let Err(e) = worktree.remove().await {
+                tracing::warn!(session_id = %id, error = %e, "Failed to remove worktree");
+            }
+        }
+
+        // Porter's own directories for the session; an explicit working
+        // directory belongs to the user and is left alone
+        let mut dirs = vec![sessions_dir.join(id)];
+        if let Ok(snapshots) = porter_dir("snapshots") {
+            dirs.push(snapshots.join(id));
+        }
+        if let Some(dir) = session.working_directory.as_deref().map(std::path::PathBuf::from) {
+            if shared {
+                dirs.retain(|d| *d != dir);
+            } else if dir.starts_with(&sessions_dir) && !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        for dir in dirs {
+            if dir.is_dir() {
+                if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+                    tracing::warn!(session_id = %id, dir = %dir.display(), error = %e, "Failed to remove session directory");
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Files in `id`'s working directory, or in its snapshot `snapshot`.
+    /// `None` if the session or snapshot doesn't exist.
+    pub async fn list_files(
+        &self,
+        id: &str,
+        snapshot: Option<&str>,
+    ) -> Result<Option<Vec<SessionFile>>> {
+        let Some(root) = self.files_root(id, snapshot).await? else {
+            return Ok(None);
+        };
+        Ok(Some(tokio::task::spawn_blocking(move || files::list(&root)).await??))
     }
-}
 
-/// Build a temporary MCP config JSON file for the Claude CLI.
-fn build_mcp_config(
-    mcp_servers: &HashMap<String, McpServerConfig>,
-) -> Result<Option<tempfile::NamedTempFile>> {
-    if mcp_servers.is_empty() {
-        return Ok(None);
+    /// Where to read file `path` of `id`'s working directory (or snapshot).
+    /// `None` if there is no such file.
+    pub async fn file_path(
+        &self,
+        id: &str,
+        path: &str,
+        snapshot: Option<&str>,
+    ) -> Result<Option<std::path::PathBuf>> {
+        let Some(root) = self.files_root(id, snapshot).await? else {
+            return Ok(None);
+        };
+        Ok(files::resolve(&root, path))
     }
 
-    let mut servers = serde_json::Map::new();
+    /// Names of `id`'s file snapshots, oldest first; `None` if the session
+    /// doesn't exist.
+    pub async fn list_snapshots(&self, id: &str) -> Result<Option<Vec<String>>> {
+        if self.db.get_agent_session(id).await?.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(files::snapshots(&porter_dir("snapshots")?.join(id))?))
+    }
 
-    for (name, config) in mcp_servers {
-        let mut env_map = serde_json::Map::new();
-        for (key, value) in &config.env {
-            let resolved = if let Some(env_key) = value.strip_prefix("env:") {
-                std::env::var(env_key).unwrap_or_default()
-            } else {
-                value.clone()
-            };
-            env_map.insert(key.clone(), serde_json::Value::String(resolved));
+    async fn files_root(
+        &self,
+        id: &str,
+        snapshot: Option<&str>,
+    ) -> Result<Option<std::path::PathBuf>> {
+        let Some(session) = self.db.get_agent_session(id).await? else {
+            return Ok(None);
+        };
+        match snapshot {
+            Some(name) => {
+                let dir = porter_dir("snapshots")?.join(id);
+                let exists = files::snapshots(&dir)?.iter().any(|s| s == name);
+                Ok(exists.then(|| dir.join(name)))
+            }
+            // Not run yet: nothing to list
+            None => Ok(Some(session.working_directory.unwrap_or_default().into())),
         }
+    }
 
-        let server = serde_json::json!({
-            "command": config.command,
-            "args": config.args,
-            "env": env_map,
-        });
+    /// Delete the sessions `[agents.retention]` no longer keeps, or with
+    /// `dry_run` just list them. Returns the sessions, oldest first.
+    ///
+    /// Sessions whose worktree has work not merged yet are kept, since
+    /// deleting them deletes their branch.
+    pub async fn prune_sessions(&self, dry_run: bool) -> Result<Vec<AgentSession>> {
+        let sessions = self.db.list_agent_sessions(None).await?;
+        let mut expired = Vec::new();
+        for session in retention::expired(&sessions, &self.retention, chrono::Utc::now()) {
+            if let Some(worktree) = Worktree::of(session) {
+                match worktree.has_unmerged_work().await {
+                    Ok(false) => {}
+                    Ok(true) => continue,
+                    Err(e) => {
+                        tracing::warn!(session_id = %session.id, error = %e, "Keeping session whose worktree can't be checked");
+                        continue;
+                    }
+                }
+            }
+            expired.push(session.clone());
+        }
+
+        if dry_run {
+            return Ok(expired);
+        }
+        let mut pruned = Vec::new();
+        for session in expired {
+            match self.delete_session(&session.id).await {
+                Ok(true) => pruned.push(session),
+                Ok(false) => {}
+                // Started again since it was listed
+                Err(e) if e.downcast_ref::<SessionBusy>().is_some() => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !pruned.is_empty() {
+            tracing::info!(count = pruned.len(), "Pruned agent sessions");
+        }
+        Ok(pruned)
+    }
+
+    /// Star or unstar a session; returns it, or `None` if it doesn't exist.
+    pub async fn set_starred(&self, id: &str, starred: bool) -> Result<Option<AgentSession>> {
+        if !self.db.set_session_starred(id, starred).await? {
+            return Ok(None);
+        }
+        self.db.get_agent_session(id).await
+    }
 
-        servers.insert(name.clone(), server);
+    /// The changes `id` has made in its worktree, as a diff against the
+    /// repository's current branch. `None` if the session doesn't exist.
+    pub async fn worktree_diff(&self, id: &str) -> Result<Option<String>> {
+        match self.session_worktree(id, false).await? {
+            Some((_, worktree)) => Ok(Some(worktree.diff().await?)),
+            None => Ok(None),
+        }
     }
 
-    let mcp_json = serde_json::json!({ "mcpServers": servers });
+    /// Commit `id`'s outstanding changes to its branch and merge the branch
+    /// into the repository's current branch. The worktree is kept, so the
+    /// session can carry on. Returns false if the session doesn't exist.
+    pub async fn merge_worktree(&self, id: &str) -> Result<bool> {
+        let Some((session, worktree)) = self.session_worktree(id, true).await? else {
+            return Ok(false);
+        };
+        let summary: String = session
+            .prompt
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .take(72)
+            .collect();
+        worktree.merge(&summary).await?;
+
+        tracing::info!(session_id = %id, branch = %worktree.branch, "Merged worktree");
+        self.db
+            .add_agent_message(
+                id,
+                "system",
+                &format!("Merged {} into the repository.", worktree.branch),
+            )
+            .await?;
+        Ok(true)
+    }
 
-    let file = tempfile::Builder::new()
-        .prefix("porter-mcp-")
-        .suffix(".json")
-        .tempfile()?;
+    /// Throw away `id`'s worktree and branch. The session keeps its
+    /// transcript but can't continue. Refused while a fork shares the
+    /// worktree. Returns false if the session doesn't exist.
+    pub async fn discard_worktree(&self, id: &str) -> Result<bool> {
+        let Some((session, worktree)) = self.session_worktree(id, true).await? else {
+            return Ok(false);
+        };
+        let path = worktree.path.to_string_lossy();
+        if self.db.working_directory_shared(&session.id, &path).await? {
+            return Err(InvalidSessionOptions(
+                "Worktree is shared with a forked session".to_string(),
+            )
+            .into());
+        }
+        // Stop a persistent process idling in the worktree
+        self.live_inputs.lock().unwrap().remove(id);
+        worktree.remove().await?;
+        self.db.clear_worktree(id).await?;
 
-    // Write and flush in a block so the BufWriter is dropped before we move `file`
-    {
-        use std::io::Write;
-        let mut f = std::io::BufWriter::new(&file);
-        serde_json::to_writer(&mut f, &mcp_json)?;
-        f.flush()?;
+        tracing::info!(session_id = %id, branch = %worktree.branch, "Discarded worktree");
+        self.db
+            .add_agent_message(
+                id,
+                "system",
+                &format!("Discarded {} and its worktree.", worktree.branch),
+            )
+            .await?;
+        Ok(true)
     }
 
-    tracing::debug!(
-        path = %file.path().display(),
-        servers = ?mcp_servers.keys().collect::<Vec<_>>(),
-        "Wrote MCP config"
+    /// Load session `id` and its worktree, refusing sessions without one and,
+    /// if `idle`, sessions still working.
+    async fn session_worktree(
+        &self,
+        id: &str,
+        idle: bool,
+    ) -> Result<Option<(AgentSession, Worktree)>> {
+        let Some(session) = self.db.get_agent_session(id).await? else {
+            return Ok(None);
+        };
+        let worktree = Worktree::of(&session)
+            .ok_or_else(|| InvalidSessionOptions("Session has no worktree".to_string()))?;
+        if idle && matches!(session.status, AgentStatus::Running | AgentStatus::Queued) {
+            return Err(SessionBusy.into());
+        }
+        Ok(Some((session, worktree)))
+    }
+}
+
+/// The prompt for a session started from a task.
+fn task_prompt(task: &Task, instructions: Option<&str>) -> String {
+    let mut prompt = format!(
+        "You are working on this task from Porter (task ID {}):\n\nTitle: {}\n",
+        task.id, task.title
     );
+    if let Some(description) = task.description.as_deref().filter(|d| !d.is_empty()) {
+        prompt.push_str(&format!("Description: {description}\n"));
+    }
+    prompt.push_str(&format!("Priority: {}\n", task.priority.as_str()));
+    if !task.tags.is_empty() {
+        prompt.push_str(&format!("Tags: {}\n", task.tags.join(", ")));
+    }
+    if let Some(due) = task.due_date {
+        prompt.push_str(&format!("Due: {}\n", due.to_rfc3339()));
+    }
 
-    Ok(Some(file))
+    prompt.push('\n');
+    prompt.push_str(instructions.unwrap_or("Complete this task."));
+    prompt
 }
 
 /// Resolve the working directory for a Claude subprocess.
-/// If an explicit directory was provided, use it. Otherwise create a session
-/// directory in ~/.porter/sessions/{session_id} so it persists across resumes.
+/// If an explicit directory was provided, use it if `security` allows it.
+/// Otherwise create a session directory in ~/.porter/sessions/{session_id}
+/// so it persists across resumes.
 fn resolve_working_dir(
-    explicit: Option<&str>,
-    session_id: &str,
+    session: &AgentSession,
+    security: &SecurityPolicy,
 ) -> Result<std::path::PathBuf> {
-    if let Some(dir) = explicit {
+    if let Some(dir) = session.working_directory.as_deref() {
+        // A worktree lives under ~/.porter; its repository is what's allowed.
+        // Porter's own session directories are always allowed.
+        match session.worktree_repo.as_deref() {
+            Some(repo) => {
+                security.check_directory(repo)?;
+            }
+            None if std::path::Path::new(dir).starts_with(porter_dir("sessions")?) => {}
+            None => return security.check_directory(dir),
+        }
+
         let path = std::path::PathBuf::from(dir);
         if path.is_dir() {
             return Ok(path);
@@ -336,350 +1449,448 @@ fn resolve_working_dir(
     }
 
     // Create a persistent session directory in ~/.porter/sessions/{session_id}
-    let home = std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .map_err(|_| anyhow::anyhow!("Could not determine home directory"))?;
+    let session_dir = porter_dir("sessions")?.join(session.id.as_str());
+    std::fs::create_dir_all(&session_dir)?;
 
-    let sessions_dir = std::path::PathBuf::from(home)
-        .join(".porter")
-        .join("sessions");
+    Ok(session_dir)
+}
 
-    std::fs::create_dir_all(&sessions_dir)?;
+/// Remove `session_id`'s channel to a run if `closed` says the run has
+/// dropped its end. Returns whether it did.
+fn remove_ended<T>(
+    channels: &Mutex<HashMap<String, T>>,
+    session_id: &str,
+    closed: impl Fn(&T) -> bool,
+) -> bool {
+    let mut channels = channels.lock().unwrap();
+    if !channels.get(session_id).is_some_and(closed) {
+        return false;
+    }
+    channels.remove(session_id);
+    true
+}
 
-    let session_dir = sessions_dir.join(session_id);
-    std::fs::create_dir_all(&session_dir)?;
+/// `~/.porter/{name}`, created if missing.
+fn porter_dir(name: &str) -> Result<std::path::PathBuf> {
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .map_err(|_| anyhow::anyhow!("Could not determine home directory"))?;
 
-    Ok(session_dir)
+    let dir = std::path::PathBuf::from(home).join(".porter").join(name);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
 }
 
-/// Apply common flags to a Claude command: CWD, --dangerously-skip-permissions,
-/// MCP config, output format, and stdio piping.
-fn configure_cmd(
-    cmd: &mut Command,
-    cwd: &std::path::Path,
-    skip_permissions: bool,
-    mcp_config_file: &Option<tempfile::NamedTempFile>,
-) {
-    cmd.current_dir(cwd)
-        .arg("--print")
-        .arg("--output-format")
-        .arg("stream-json")
-        .arg("--verbose")
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-
-    if skip_permissions {
-        cmd.arg("--dangerously-skip-permissions");
-    }
-
-    if let Some(ref config_file) = mcp_config_file {
-        cmd.arg("--mcp-config").arg(config_file.path());
+/// The next follow-up for a live run; never resolves without one.
+async fn next_message(live: &mut Option<LiveInput>) -> Option<String> {
+    match live {
+        Some(input) => input.messages.recv().await,
+        None => std::future::pending().await,
     }
 }
 
-/// Process streaming JSON output from a Claude subprocess line by line.
-/// Extracts assistant text content, broadcasts chunks, and returns the
-/// accumulated assistant text and (optionally) the Claude session ID.
-async fn process_stream(
-    child: &mut tokio::process::Child,
+/// Drive a run to its end, recording its events as they arrive: every
+/// content block (text, thinking, tool calls and their results) is persisted
+/// to the transcript and broadcast to subscribers. For a live run, follow-ups
+/// are sent between turns and its input is closed once it has been idle for
+/// `LiveInput::idle`.
+///
+/// Time spent waiting for the user to answer permission requests doesn't
+/// count towards `timeouts`.
+async fn drive_run(
+    run: &mut dyn AgentRun,
     session_id: &str,
-    event_tx: &broadcast::Sender<AgentEvent>,
-) -> Result<(String, Option<String>)> {
-    let stdout = child
-        .stdout
-        .take()
-        .ok_or_else(|| anyhow::anyhow!("Failed to capture stdout"))?;
-
-    let mut reader = BufReader::new(stdout).lines();
-    let mut accumulated_text = String::new();
-    let mut claude_session_id: Option<String> = None;
+    timeouts: RunTimeouts,
+    mut live: Option<LiveInput>,
+    manager: &AgentManager,
+) -> Result<()> {
+    let (db, budget) = (&manager.db, &manager.budget);
+    let mut transcript = Transcript::new(session_id, db, &manager.event_tx);
     let mut first_event = true;
+    // None while a live run waits between turns
+    let mut turn_started = Some(RunClock::start(manager, session_id));
+    let mut last_event = RunClock::start(manager, session_id);
 
     loop {
         // Apply a short startup timeout for the first event (covers MCP server init).
-        // After that, rely on the outer SESSION_TIMEOUT for the full run.
-        let line = if first_event {
-            match tokio::time::timeout(STARTUP_TIMEOUT, reader.next_line()).await {
-                Ok(result) => result?,
-                Err(_) => {
-                    anyhow::bail!(
-                        "No output within {} seconds — MCP server may have failed to start",
-                        STARTUP_TIMEOUT.as_secs()
-                    );
+        let limit = match (first_event, turn_started) {
+            (true, _) => Some(timeouts.startup),
+            (false, Some(turn)) => Some(timeouts.remaining(turn, last_event, manager, session_id)),
+            (false, None) => live.as_ref().map(|input| input.idle),
+        };
+        // While the user is being asked the clock stands still; look again
+        // now and then rather than spinning on a deadline that doesn't move
+        let asking = turn_started.is_some() && manager.permissions.waited(session_id).1;
+        let wait = limit.map(|l| if asking { l.max(PERMISSION_RECHECK) } else { l });
+        let accepting = live.is_some() && turn_started.is_none();
+        let next = async {
+            match wait {
+                Some(wait) => tokio::time::timeout(wait, run.next_event()).await.ok(),
+                None => Some(run.next_event().await),
+            }
+        };
+
+        let event = tokio::select! {
+            event = next => event,
+            message = next_message(&mut live), if accepting => {
+                match message {
+                    Some(text) => {
+                        run.send(&text).await?;
+                        turn_started = Some(RunClock::start(manager, session_id));
+                        last_event = RunClock::start(manager, session_id);
+                        transcript.has_text = false;
+                    }
+                    // Input closed (shutdown): let the run end
+                    None => {
+                        run.close_input();
+                        live = None;
+                    }
                 }
+                continue;
             }
-        } else {
-            reader.next_line().await?
         };
 
-        let Some(line) = line else { break };
-        let parsed: serde_json::Value = match serde_json::from_str(&line) {
-            Ok(v) => v,
-            Err(_) => continue,
+        let event = match event {
+            Some(event) => event?,
+            None if first_event => {
+                anyhow::bail!(
+                    "No output within {} seconds — MCP server may have failed to start",
+                    timeouts.startup.as_secs()
+                );
+            }
+            None if turn_started.is_none() => {
+                tracing::info!(session_id = %session_id, "Stopping idle agent run");
+                run.close_input();
+                break;
+            }
+            // Time ran out only counting the wait for permission
+            None if manager.permissions.waited(session_id).1
+                || turn_started.is_some_and(|turn| {
+                    !timeouts.remaining(turn, last_event, manager, session_id).is_zero()
+                }) =>
+            {
+                continue;
+            }
+            None if timeouts.mode == TimeoutMode::Idle => {
+                anyhow::bail!("Session idle for {} seconds, stopped", timeouts.limit.as_secs());
+            }
+            None => {
+                anyhow::bail!("Session timed out after {} seconds", timeouts.limit.as_secs());
+            }
         };
 
+        let Some(event) = event else { break };
         first_event = false;
-        let event_type = parsed["type"].as_str().unwrap_or("");
-
-        match event_type {
-            "system" => {
-                // Extract session_id from init event
-                if parsed["subtype"].as_str() == Some("init") {
-                    if let Some(sid) = parsed["session_id"].as_str() {
-                        claude_session_id = Some(sid.to_string());
-                    }
-                    if let Some(servers) = parsed["mcp_servers"].as_array() {
-                        let names: Vec<&str> =
-                            servers.iter().filter_map(|s| s.as_str()).collect();
-                        tracing::info!(
-                            session_id = %session_id,
-                            mcp_servers = ?names,
-                            "Claude session initialized"
-                        );
-                    }
-                }
+        last_event = RunClock::start(manager, session_id);
+
+        match event {
+            BackendEvent::Started {
+                conversation_id,
+                detail,
+            } => {
+                // Saved straight away so the session can be resumed even if
+                // this run fails
+                db.set_claude_session_id(session_id, &conversation_id).await?;
+                transcript
+                    .record(AgentMessage::new(session_id, "system", "init", &detail))
+                    .await;
+            }
+            BackendEvent::Text(text) => transcript.text(&text).await,
+            BackendEvent::Thinking(thinking) => transcript.thinking(&thinking).await,
+            BackendEvent::ToolUse { id, name, input } => {
+                transcript.tool_use(id, &name, input).await
             }
-            "assistant" => {
-                // Parse all content blocks from the assistant message
-                if let Some(content) = parsed["message"]["content"].as_array() {
-                    for block in content {
-                        match block["type"].as_str() {
-                            Some("text") => {
-                                if let Some(text) = block["text"].as_str() {
-                                    accumulated_text.push_str(text);
-                                    let _ = event_tx.send(AgentEvent::Output {
-                                        session_id: session_id.to_string(),
-                                        content: text.to_string(),
-                                        content_type: "text".to_string(),
-                                    });
-                                }
-                            }
-                            Some("thinking") => {
-                                if let Some(thinking) = block["thinking"].as_str() {
-                                    let _ = event_tx.send(AgentEvent::Output {
-                                        session_id: session_id.to_string(),
-                                        content: thinking.to_string(),
-                                        content_type: "thinking".to_string(),
-                                    });
-                                }
-                            }
-                            Some("tool_use") => {
-                                if let Some(name) = block["name"].as_str() {
-                                    let _ = event_tx.send(AgentEvent::Output {
-                                        session_id: session_id.to_string(),
-                                        content: name.to_string(),
-                                        content_type: "tool_use".to_string(),
-                                    });
-                                }
-                            }
-                            _ => {}
+            BackendEvent::ToolResult {
+                tool_use_id,
+                content,
+                is_error,
+            } => transcript.tool_result(tool_use_id, &content, is_error).await,
+            BackendEvent::TurnComplete { usage, text, error } => {
+                // Record what the turn cost, even if it ended in an error
+                if let Some(usage) = usage {
+                    if let Err(e) = db.record_agent_usage(session_id, &usage).await {
+                        tracing::warn!(session_id = %session_id, error = %e, "Failed to record usage");
+                    }
+                    if let Err(e) = budget.after_run(session_id, usage.cost_usd).await {
+                        // Over its cap: cancel the run like a user would,
+                        // and wait for run_agent to stop it
+                        if !manager.cancel_over_budget(session_id, &e) {
+                            return Err(e);
                         }
+                        std::future::pending::<()>().await;
                     }
                 }
-            }
-            "result" => {
-                // Check for error results (e.g. failed resume)
-                if parsed["is_error"].as_bool() == Some(true) {
-                    let errors = parsed["errors"]
-                        .as_array()
-                        .map(|arr| {
-                            arr.iter()
-                                .filter_map(|e| e.as_str())
-                                .collect::<Vec<_>>()
-                                .join("; ")
-                        })
-                        .unwrap_or_else(|| "Unknown error".to_string());
-                    tracing::error!(
-                        session_id = %session_id,
-                        "Claude error result: {errors}"
-                    );
-                    anyhow::bail!("{errors}");
+
+                // Error results (e.g. a failed resume) fail the run
+                if let Some(error) = error {
+                    tracing::error!(session_id = %session_id, "Agent error result: {error}");
+                    anyhow::bail!("{error}");
                 }
-                // Final result — use its text if we haven't accumulated any
-                if accumulated_text.is_empty() {
-                    if let Some(text) = parsed["result"].as_str() {
-                        accumulated_text = text.to_string();
-                        let _ = event_tx.send(AgentEvent::Output {
-                            session_id: session_id.to_string(),
-                            content: text.to_string(),
-                            content_type: "text".to_string(),
-                        });
+                // Final result — use its text if the stream carried none
+                if !transcript.has_text {
+                    if let Some(text) = text {
+                        transcript.text(&text).await;
                     }
                 }
+
+                if let Some(ref input) = live {
+                    turn_started = None;
+                    let _ = input.turn_done.send(());
+                }
             }
-            _ => {}
         }
     }
 
-    Ok((accumulated_text, claude_session_id))
+    Ok(())
 }
 
-/// Drain stderr and log it.
-async fn drain_stderr(child: &mut tokio::process::Child, session_id: &str) {
-    if let Some(mut stderr) = child.stderr.take() {
-        let mut buf = String::new();
-        if tokio::io::AsyncReadExt::read_to_string(&mut stderr, &mut buf)
-            .await
-            .is_ok()
-            && !buf.is_empty()
-        {
-            tracing::warn!(session_id = %session_id, stderr = %buf, "Claude stderr");
-        }
-    }
+/// Writes run events into `agent_messages` and broadcasts them. Claude
+/// may repeat a tool call or result across successive events, so ones whose
+/// id was already seen in this run are skipped; text is never deduplicated,
+/// as an agent may legitimately say the same thing twice.
+struct Transcript<'a> {
+    session_id: &'a str,
+    db: &'a Database,
+    event_tx: &'a broadcast::Sender<AgentEvent>,
+    /// `tool_use:{id}` and `tool_result:{id}` keys already recorded.
+    seen: HashSet<String>,
+    /// Tool names by tool_use id, so results can be labelled.
+    tool_names: HashMap<String, String>,
+    has_text: bool,
 }
 
-/// Run a Claude subprocess with a timeout and cancellation support.
-async fn run_with_timeout(
-    child: &mut tokio::process::Child,
-    session_id: &str,
-    event_tx: &broadcast::Sender<AgentEvent>,
-    cancel_rx: oneshot::Receiver<()>,
-) -> Result<(String, Option<String>)> {
-    tokio::select! {
-        result = tokio::time::timeout(SESSION_TIMEOUT, process_stream(child, session_id, event_tx)) => {
-            match result {
-                Ok(inner) => inner,
-                Err(_) => {
-                    tracing::error!(session_id = %session_id, "Claude session timed out, killing process");
-                    // Try to capture stderr before killing
-                    if let Some(mut stderr) = child.stderr.take() {
-                        let mut buf = String::new();
-                        use tokio::io::AsyncReadExt;
-                        if let Ok(_) = tokio::time::timeout(
-                            std::time::Duration::from_secs(1),
-                            stderr.read_to_string(&mut buf)
-                        ).await {
-                            if !buf.is_empty() {
-                                tracing::error!(session_id = %session_id, stderr = %buf, "Claude stderr on timeout");
-                            }
-                        }
-                    }
-                    let _ = child.kill().await;
-                    anyhow::bail!("Session timed out after {} seconds", SESSION_TIMEOUT.as_secs());
-                }
-            }
-        }
-        _ = cancel_rx => {
-            tracing::info!(session_id = %session_id, "Session cancelled, killing process");
-            let _ = child.kill().await;
-            anyhow::bail!("Session was cancelled");
+impl<'a> Transcript<'a> {
+    fn new(
+        session_id: &'a str,
+        db: &'a Database,
+        event_tx: &'a broadcast::Sender<AgentEvent>,
+    ) -> Self {
+        Self {
+            session_id,
+            db,
+            event_tx,
+            seen: HashSet::new(),
+            tool_names: HashMap::new(),
+            has_text: false,
         }
     }
-}
 
-#[allow(clippy::too_many_arguments)]
-async fn run_claude_session(
-    claude_binary: &str,
-    prompt: &str,
-    session_id: &str,
-    mcp_servers: &HashMap<String, McpServerConfig>,
-    working_directory: Option<&str>,
-    skip_permissions: bool,
-    db: &Database,
-    event_tx: &broadcast::Sender<AgentEvent>,
-    cancel_rx: oneshot::Receiver<()>,
-) -> Result<()> {
-    db.add_agent_message(session_id, "user", prompt).await?;
+    async fn text(&mut self, text: &str) {
+        self.has_text = true;
+        self.assistant(AgentMessage::new(self.session_id, "assistant", "text", text))
+            .await;
+    }
 
-    let mcp_config_file = build_mcp_config(mcp_servers)?;
-    let cwd = resolve_working_dir(working_directory, session_id)?;
+    async fn thinking(&mut self, thinking: &str) {
+        self.assistant(AgentMessage::new(self.session_id, "assistant", "thinking", thinking))
+            .await;
+    }
 
-    // Save the working directory immediately so resume can use it
-    if working_directory.is_none() {
-        db.set_working_directory(session_id, cwd.to_str().unwrap_or_default())
-            .await?;
+    async fn tool_use(&mut self, id: Option<String>, name: &str, input: serde_json::Value) {
+        if let Some(ref id) = id {
+            self.tool_names.insert(id.clone(), name.to_string());
+        }
+        self.assistant(AgentMessage {
+            tool_name: Some(name.to_string()),
+            tool_input: Some(input),
+            tool_use_id: id,
+            ..AgentMessage::new(self.session_id, "assistant", "tool_use", name)
+        })
+        .await;
     }
 
-    let mut cmd = Command::new(claude_binary);
-    configure_cmd(&mut cmd, &cwd, skip_permissions, &mcp_config_file);
+    async fn assistant(&mut self, msg: AgentMessage) {
+        if let Some(ref id) = msg.tool_use_id {
+            if !self.seen.insert(format!("tool_use:{id}")) {
+                return;
+            }
+        }
 
-    // Append the prompt as a system prompt if MCP servers are configured,
-    // so the agent knows what tools are available via MCP.
-    if !mcp_servers.is_empty() {
-        let server_list: Vec<&str> = mcp_servers.keys().map(|s| s.as_str()).collect();
-        let system_note = format!(
-            "You have access to MCP servers: {}. Use them when relevant.",
-            server_list.join(", ")
-        );
-        cmd.arg("--append-system-prompt").arg(&system_note);
+        let _ = self.event_tx.send(AgentEvent::Output {
+            session_id: self.session_id.to_string(),
+            content: msg.content.clone(),
+            content_type: msg.content_type.clone(),
+        });
+        self.record(msg).await;
     }
 
-    cmd.arg(prompt);
-
-    tracing::info!(
-        session_id = %session_id,
-        cwd = %cwd.display(),
-        mcp = ?mcp_servers.keys().collect::<Vec<_>>(),
-        skip_permissions,
-        "Starting Claude session"
-    );
+    async fn tool_result(&mut self, tool_use_id: Option<String>, content: &str, is_error: bool) {
+        if let Some(ref id) = tool_use_id {
+            if !self.seen.insert(format!("tool_result:{id}")) {
+                return;
+            }
+        }
 
-    let mut child = cmd.spawn()?;
-    let (text, claude_sid) = run_with_timeout(&mut child, session_id, event_tx, cancel_rx).await?;
+        let tool_name = tool_use_id
+            .as_ref()
+            .and_then(|id| self.tool_names.get(id).cloned());
+        self.record(AgentMessage {
+            tool_name,
+            tool_use_id,
+            is_error,
+            ..AgentMessage::new(self.session_id, "tool", "tool_result", content)
+        })
+        .await;
+    }
 
-    if let Some(ref csid) = claude_sid {
-        db.set_claude_session_id(session_id, csid).await?;
+    /// Persist a transcript row. Failures are logged rather than aborting
+    /// the session — losing a row is better than killing the agent.
+    async fn record(&self, msg: AgentMessage) {
+        if let Err(e) = self.db.insert_agent_message(&msg).await {
+            tracing::warn!(
+                session_id = %self.session_id,
+                content_type = %msg.content_type,
+                error = %e,
+                "Failed to store transcript entry"
+            );
+        }
     }
+}
+
+/// Time limits for one agent run.
+#[derive(Debug, Clone, Copy)]
+struct RunTimeouts {
+    /// Until the first event.
+    startup: Duration,
+    /// For each turn, or between events in `Idle` mode.
+    limit: Duration,
+    mode: TimeoutMode,
+}
 
-    drain_stderr(&mut child, session_id).await;
-    let status = child.wait().await?;
+/// Everything needed to start one run for a session.
+struct RunSpec {
+    /// `None` if the session's backend is no longer configured.
+    backend: Option<Arc<dyn AgentBackend>>,
+    session: AgentSession,
+    /// The session prompt, or a follow-up message when resuming.
+    prompt: String,
+    resume: Option<Resume>,
+    mcp_servers: HashMap<String, McpServerConfig>,
+    permission_tool: Option<&'static str>,
+    timeouts: RunTimeouts,
+    /// Checked again at each run, for sessions created under an older config.
+    security: SecurityPolicy,
+}
 
-    if !text.is_empty() {
-        db.add_agent_message(session_id, "assistant", &text).await?;
+/// Measures time in a run, leaving out time spent waiting for the user to
+/// answer permission requests.
+#[derive(Debug, Clone, Copy)]
+struct RunClock {
+    started: Instant,
+    /// `Permissions::waited` when started.
+    waited: Duration,
+}
+
+impl RunClock {
+    fn start(manager: &AgentManager, session_id: &str) -> Self {
+        Self {
+            started: Instant::now(),
+            waited: manager.permissions.waited(session_id).0,
+        }
     }
 
-    if !status.success() {
-        anyhow::bail!("Claude process exited with status: {}", status);
+    fn elapsed(&self, manager: &AgentManager, session_id: &str) -> Duration {
+        let waited = manager.permissions.waited(session_id).0;
+        self.started
+            .elapsed()
+            .saturating_sub(waited.saturating_sub(self.waited))
     }
+}
 
-    Ok(())
+impl RunTimeouts {
+    /// Time left in the current turn, which started at `turn` and last
+    /// produced an event at `last_event`.
+    fn remaining(
+        &self,
+        turn: RunClock,
+        last_event: RunClock,
+        manager: &AgentManager,
+        session_id: &str,
+    ) -> Duration {
+        let elapsed = match self.mode {
+            TimeoutMode::Total => turn.elapsed(manager, session_id),
+            TimeoutMode::Idle => last_event.elapsed(manager, session_id),
+        };
+        self.limit.saturating_sub(elapsed)
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn resume_claude_session(
-    claude_binary: &str,
-    claude_session_id: &str,
-    prompt: &str,
-    session_id: &str,
-    mcp_servers: &HashMap<String, McpServerConfig>,
-    working_directory: Option<&str>,
-    skip_permissions: bool,
-    db: &Database,
-    event_tx: &broadcast::Sender<AgentEvent>,
+/// Follow-up messages for a run kept alive between turns.
+struct LiveInput {
+    messages: mpsc::UnboundedReceiver<String>,
+    /// Signalled each time a turn completes successfully.
+    turn_done: mpsc::UnboundedSender<()>,
+    /// How long the run may wait for a message before it is stopped.
+    idle: Duration,
+}
+
+/// Run a session on its backend until the run ends, fails, times out or is
+/// cancelled. With `live` it stays up between turns for follow-ups;
+/// otherwise it answers the prompt and ends.
+async fn run_agent(
+    spec: RunSpec,
+    live: Option<LiveInput>,
+    manager: &AgentManager,
     cancel_rx: oneshot::Receiver<()>,
 ) -> Result<()> {
-    // Don't pass MCP config during resume - the session already has its servers initialized
-    let cwd = resolve_working_dir(working_directory, session_id)?;
-
-    let mut cmd = Command::new(claude_binary);
-    cmd.arg("--resume").arg(claude_session_id);
-    // Pass None for mcp_config_file since we don't want to reinitialize MCP servers on resume
-    configure_cmd(&mut cmd, &cwd, skip_permissions, &None);
-    cmd.arg(prompt);
-
-    tracing::info!(
-        session_id = %session_id,
-        claude_session_id = %claude_session_id,
-        cwd = %cwd.display(),
-        mcp = ?mcp_servers.keys().collect::<Vec<_>>(),
-        skip_permissions,
-        "Resuming Claude session"
-    );
+    let db = &manager.db;
+    let session = &spec.session;
+    let session_id = session.id.as_str();
+    if spec.resume.is_none() {
+        db.add_agent_message(session_id, "user", &spec.prompt).await?;
+    }
 
-    let mut child = cmd.spawn()?;
-    let (text, _) = run_with_timeout(&mut child, session_id, event_tx, cancel_rx).await?;
+    let backend = spec.backend.as_ref().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Backend '{}' is not configured",
+            session.backend.as_deref().unwrap_or_default()
+        )
+    })?;
 
-    drain_stderr(&mut child, session_id).await;
-    let status = child.wait().await?;
+    spec.security
+        .check_permissions(session.dangerously_skip_permissions, session.permission_mode)?;
+    let cwd = resolve_working_dir(session, &spec.security)?;
 
-    if !text.is_empty() {
-        db.add_agent_message(session_id, "assistant", &text).await?;
+    // Save the working directory immediately so resume can use it
+    if session.working_directory.is_none() {
+        db.set_working_directory(session_id, cwd.to_str().unwrap_or_default())
+            .await?;
     }
 
-    if !status.success() {
-        anyhow::bail!("Claude resume exited with status: {}", status);
+    let request = RunRequest {
+        session,
+        cwd: &cwd,
+        prompt: &spec.prompt,
+        mcp_servers: &spec.mcp_servers,
+        permission_tool: spec.permission_tool,
+        live: live.is_some(),
+    };
+    let mut run = match spec.resume {
+        Some(ref resume) => backend.resume(&request, resume).await?,
+        None => backend.spawn(&request).await?,
+    };
+
+    tokio::select! {
+        result = drive_run(run.as_mut(), session_id, spec.timeouts, live, manager) => {
+            if let Err(e) = result {
+                // Stop the run if we gave up on it mid-stream (e.g. a timeout)
+                tracing::error!(session_id = %session_id, error = %e, "Stopping agent run");
+                run.cancel().await;
+                return Err(e);
+            }
+        }
+        _ = cancel_rx => {
+            let over_budget = manager.over_budget.lock().unwrap().remove(session_id);
+            if let Some(reason) = over_budget {
+                tracing::warn!(session_id = %session_id, "{reason}, stopping run");
+                run.cancel().await;
+                return Err(BudgetExceeded(reason).into());
+            }
+            tracing::info!(session_id = %session_id, "Session cancelled, stopping run");
+            run.cancel().await;
+            anyhow::bail!("Session was cancelled");
+        }
     }
 
-    Ok(())
+    run.finish().await
 }
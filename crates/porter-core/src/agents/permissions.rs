@@ -0,0 +1,248 @@
+use super::{AgentEvent, InvalidSessionOptions};
+use crate::db::Database;
+use crate::models::{
+    AgentSession, CreatePermissionRule, PermissionDecision, PermissionRequest, PermissionRule,
+    PermissionScope, ResolvePermission,
+};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, oneshot};
+use tokio_util::sync::CancellationToken;
+
+/// Tool requests from Claude's `--permission-prompt-tool` waiting on the user.
+///
+/// Each request blocks the Claude run (its MCP tool call is held open) until
+/// the user answers, an allow-rule matches, or `timeout` passes, in which
+/// case it is denied. Requests are also denied once Porter starts shutting
+/// down, so they don't hold the shutdown up. Time sessions spend waiting is
+/// tracked so their timeouts can leave it out.
+#[derive(Clone)]
+pub(crate) struct Permissions {
+    db: Database,
+    event_tx: broadcast::Sender<AgentEvent>,
+    timeout: Duration,
+    pending: Arc<Mutex<HashMap<String, Pending>>>,
+    waits: Arc<Mutex<HashMap<String, Wait>>>,
+    closed: CancellationToken,
+}
+
+struct Pending {
+    request: PermissionRequest,
+    tx: oneshot::Sender<PermissionDecision>,
+}
+
+/// How long a session has been held up by permission requests.
+#[derive(Default)]
+struct Wait {
+    /// Requests currently open.
+    open: usize,
+    /// When the current wait began, while `open > 0`.
+    since: Option<Instant>,
+    /// Completed waits.
+    total: Duration,
+}
+
+/// Ends a wait when the request is answered or abandoned.
+struct WaitGuard<'a> {
+    waits: &'a Mutex<HashMap<String, Wait>>,
+    session_id: &'a str,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(wait) = self.waits.lock().unwrap().get_mut(self.session_id) {
+            wait.open -= 1;
+            if wait.open == 0 {
+                wait.total += wait.since.take().map(|t| t.elapsed()).unwrap_or_default();
+            }
+        }
+    }
+}
+
+impl Permissions {
+    pub fn new(db: Database, event_tx: broadcast::Sender<AgentEvent>, timeout: Duration) -> Self {
+        Self {
+            db,
+            event_tx,
+            timeout,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            waits: Arc::new(Mutex::new(HashMap::new())),
+            closed: CancellationToken::new(),
+        }
+    }
+
+    /// Decide whether `session` may make a tool call, asking the user unless
+    /// an allow-rule covers the tool.
+    pub async fn request(
+        &self,
+        session: &AgentSession,
+        tool_name: &str,
+        input: serde_json::Value,
+        tool_use_id: Option<&str>,
+    ) -> Result<PermissionDecision> {
+        let rules = self
+            .db
+            .list_session_permission_rules(&session.id, session.profile.as_deref())
+            .await?;
+        if rules.iter().any(|r| r.tool_name == tool_name) {
+            tracing::debug!(session_id = %session.id, tool = tool_name, "Tool allowed by rule");
+            return Ok(PermissionDecision {
+                allow: true,
+                message: None,
+            });
+        }
+
+        let request = PermissionRequest::new(&session.id, tool_name, input, tool_use_id);
+        let request_id = request.id.clone();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(
+            request_id.clone(),
+            Pending {
+                request: request.clone(),
+                tx,
+            },
+        );
+        tracing::info!(session_id = %session.id, tool = tool_name, request_id = %request_id, "Permission requested");
+        let _ = self.event_tx.send(AgentEvent::PermissionRequested(request));
+        let _wait = self.begin_wait(&session.id);
+
+        let answer = tokio::select! {
+            answer = tokio::time::timeout(self.timeout, rx) => Some(answer),
+            _ = self.closed.cancelled() => None,
+        };
+        let decision = match answer {
+            Some(Ok(Ok(decision))) => decision,
+            Some(Ok(Err(_))) => PermissionDecision {
+                allow: false,
+                message: Some("The session ended before permission was given.".to_string()),
+            },
+            Some(Err(_)) => {
+                self.pending.lock().unwrap().remove(&request_id);
+                PermissionDecision {
+                    allow: false,
+                    message: Some(format!(
+                        "No response to the permission request within {} seconds.",
+                        self.timeout.as_secs()
+                    )),
+                }
+            }
+            None => {
+                self.pending.lock().unwrap().remove(&request_id);
+                PermissionDecision {
+                    allow: false,
+                    message: Some("Porter is shutting down.".to_string()),
+                }
+            }
+        };
+
+        let verdict = if decision.allow { "allowed" } else { "denied" };
+        let _ = self
+            .db
+            .add_agent_message(&session.id, "system", &format!("Permission for {tool_name} {verdict}."))
+            .await;
+        let _ = self.event_tx.send(AgentEvent::PermissionResolved {
+            session_id: session.id.clone(),
+            request_id,
+            allow: decision.allow,
+        });
+        Ok(decision)
+    }
+
+    /// Requests for `session_id` still waiting on the user, oldest first.
+    pub fn pending(&self, session_id: &str) -> Vec<PermissionRequest> {
+        let mut requests: Vec<_> = self
+            .pending
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|p| p.request.session_id == session_id)
+            .map(|p| p.request.clone())
+            .collect();
+        requests.sort_by_key(|r| r.requested_at);
+        requests
+    }
+
+    /// Answer a pending request. Returns false if `session` has no such
+    /// request (e.g. it already timed out).
+    pub async fn resolve(
+        &self,
+        session: &AgentSession,
+        request_id: &str,
+        input: ResolvePermission,
+    ) -> Result<bool> {
+        let tool_name = {
+            let pending = self.pending.lock().unwrap();
+            match pending.get(request_id) {
+                Some(p) if p.request.session_id == session.id => p.request.tool_name.clone(),
+                _ => return Ok(false),
+            }
+        };
+
+        if let (true, Some(scope)) = (input.allow, input.remember) {
+            let rule = match scope {
+                PermissionScope::Session => CreatePermissionRule {
+                    session_id: Some(session.id.clone()),
+                    profile: None,
+                    tool_name,
+                },
+                PermissionScope::Profile => CreatePermissionRule {
+                    session_id: None,
+                    profile: Some(session.profile.clone().ok_or_else(|| {
+                        InvalidSessionOptions("Session was not started with a profile".to_string())
+                    })?),
+                    tool_name,
+                },
+            };
+            self.db.add_permission_rule(&PermissionRule::new(rule)).await?;
+        }
+
+        let Some(pending) = self.pending.lock().unwrap().remove(request_id) else {
+            return Ok(false);
+        };
+        let _ = pending.tx.send(PermissionDecision {
+            allow: input.allow,
+            message: input.message,
+        });
+        Ok(true)
+    }
+
+    /// Deny everything `session_id` is waiting on (its run has ended).
+    pub fn clear_session(&self, session_id: &str) {
+        self.pending
+            .lock()
+            .unwrap()
+            .retain(|_, p| p.request.session_id != session_id);
+        self.waits.lock().unwrap().remove(session_id);
+    }
+
+    /// Deny every pending request, and any made from now on.
+    pub fn close(&self) {
+        self.closed.cancel();
+    }
+
+    fn begin_wait<'a>(&'a self, session_id: &'a str) -> WaitGuard<'a> {
+        let mut waits = self.waits.lock().unwrap();
+        let wait = waits.entry(session_id.to_string()).or_default();
+        wait.open += 1;
+        wait.since.get_or_insert_with(Instant::now);
+        WaitGuard {
+            waits: &self.waits,
+            session_id,
+        }
+    }
+
+    /// Total time `session_id` has spent waiting on the user, so far, and
+    /// whether it is waiting now. Only differences between calls are
+    /// meaningful.
+    pub fn waited(&self, session_id: &str) -> (Duration, bool) {
+        match self.waits.lock().unwrap().get(session_id) {
+            Some(wait) => (
+                wait.total + wait.since.map(|t| t.elapsed()).unwrap_or_default(),
+                wait.open > 0,
+            ),
+            None => (Duration::ZERO, false),
+        }
+    }
+}
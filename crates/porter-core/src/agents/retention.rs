@@ -0,0 +1,164 @@
+use crate::config::RetentionConfig;
+use crate::models::{AgentSession, AgentStatus};
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+
+/// The sessions `config` says should be deleted at `now`, oldest first.
+/// Running, queued and paused sessions are never included, nor starred ones
+/// when `keep_starred` is set.
+pub(crate) fn expired<'a>(
+    sessions: &'a [AgentSession],
+    config: &RetentionConfig,
+    now: DateTime<Utc>,
+) -> Vec<&'a AgentSession> {
+    let cutoff = config
+        .max_age_days
+        .map(|days| now - chrono::Duration::days(days as i64));
+
+    let mut finished: Vec<&AgentSession> = sessions
+        .iter()
+        .filter(|s| {
+            !matches!(
+                s.status,
+                AgentStatus::Running | AgentStatus::Queued | AgentStatus::Paused
+            )
+        })
+        .filter(|s| !(config.keep_starred && s.starred))
+        .collect();
+    finished.sort_by_key(|s| Reverse(s.started_at));
+
+    let mut expired: Vec<&AgentSession> = finished
+        .into_iter()
+        .enumerate()
+        .filter(|(i, s)| {
+            let over_count = config.max_sessions.is_some_and(|max| *i >= max);
+            let too_old = cutoff.is_some_and(|c| s.completed_at.unwrap_or(s.started_at) < c);
+            over_count || too_old
+        })
+        .map(|(_, s)| s)
+        .collect();
+    expired.reverse();
+    expired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn now() -> DateTime<Utc> {
+        "2026-06-15T12:00:00Z".parse().unwrap()
+    }
+
+    /// A session named `prompt` that started `days_ago` and ended an hour
+    /// later, unless it's still running or queued.
+    fn session(prompt: &str, days_ago: i64, status: AgentStatus) -> AgentSession {
+        let mut session = AgentSession::new(prompt, "model");
+        session.status = status;
+        session.started_at = now() - Duration::days(days_ago);
+        if !matches!(status, AgentStatus::Running | AgentStatus::Queued) {
+            session.completed_at = Some(session.started_at + Duration::hours(1));
+        }
+        session
+    }
+
+    fn completed(prompt: &str, days_ago: i64) -> AgentSession {
+        session(prompt, days_ago, AgentStatus::Completed)
+    }
+
+    fn starred(mut session: AgentSession) -> AgentSession {
+        session.starred = true;
+        session
+    }
+
+    fn config(max_age_days: Option<u64>, max_sessions: Option<usize>) -> RetentionConfig {
+        RetentionConfig {
+            max_age_days,
+            max_sessions,
+            ..Default::default()
+        }
+    }
+
+    fn expired_prompts<'a>(sessions: &'a [AgentSession], config: &RetentionConfig) -> Vec<&'a str> {
+        expired(sessions, config, now())
+            .into_iter()
+            .map(|s| s.prompt.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_everything_by_default() {
+        let sessions = [completed("a", 400), completed("b", 1)];
+
+        assert!(expired_prompts(&sessions, &RetentionConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn expires_by_age() {
+        let sessions = [
+            completed("new", 1),
+            session("old", 40, AgentStatus::Failed),
+            completed("older", 50),
+            completed("edge", 30),
+        ];
+
+        assert_eq!(expired_prompts(&sessions, &config(Some(30), None)), ["older", "old"]);
+    }
+
+    #[test]
+    fn expires_by_count_oldest_first() {
+        let sessions = [
+            completed("b", 2),
+            completed("d", 4),
+            completed("a", 1),
+            completed("c", 3),
+        ];
+
+        assert_eq!(expired_prompts(&sessions, &config(None, Some(2))), ["d", "c"]);
+        assert!(expired_prompts(&sessions, &config(None, Some(4))).is_empty());
+        assert_eq!(
+            expired_prompts(&sessions, &config(None, Some(0))),
+            ["d", "c", "b", "a"]
+        );
+    }
+
+    #[test]
+    fn expires_by_either_limit() {
+        let sessions = [completed("a", 1), completed("b", 2), completed("c", 40)];
+
+        assert_eq!(expired_prompts(&sessions, &config(Some(30), Some(1))), ["c", "b"]);
+    }
+
+    #[test]
+    fn keeps_sessions_that_are_not_finished() {
+        let sessions = [
+            session("running", 40, AgentStatus::Running),
+            session("queued", 40, AgentStatus::Queued),
+            session("paused", 40, AgentStatus::Paused),
+            completed("a", 2),
+            completed("b", 3),
+        ];
+
+        assert!(expired_prompts(&sessions, &config(Some(30), None)).is_empty());
+        // Nor do they count towards max_sessions
+        assert_eq!(expired_prompts(&sessions, &config(None, Some(1))), ["b"]);
+    }
+
+    #[test]
+    fn keeps_starred_sessions_unless_configured_not_to() {
+        let sessions = [
+            starred(completed("starred", 50)),
+            completed("a", 1),
+            completed("b", 40),
+        ];
+
+        let keep = config(Some(30), Some(1));
+        assert_eq!(expired_prompts(&sessions, &keep), ["b"]);
+
+        let drop = RetentionConfig {
+            keep_starred: false,
+            ..keep
+        };
+        assert_eq!(expired_prompts(&sessions, &drop), ["starred", "b"]);
+    }
+}
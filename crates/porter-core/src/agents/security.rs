@@ -0,0 +1,200 @@
+use super::InvalidSessionOptions;
+use crate::config::SecurityConfig;
+use crate::models::PermissionMode;
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Returned (via `anyhow`) when `[agents.security]` forbids what a session
+/// asks for.
+#[derive(Debug)]
+pub struct PolicyViolation(pub String);
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PolicyViolation {}
+
+/// Enforces `[agents.security]`: where sessions may work and whether they may
+/// skip Claude's permission checks.
+///
+/// Directories are compared after canonicalisation, so `..` components and
+/// symlinks can't lead a session outside an allowed root.
+#[derive(Clone)]
+pub(crate) struct SecurityPolicy {
+    /// Canonical allowed roots; `None` allows any directory.
+    roots: Option<Vec<PathBuf>>,
+    allow_skip_permissions: bool,
+}
+
+impl SecurityPolicy {
+    pub fn new(config: &SecurityConfig) -> Self {
+        let roots = (!config.allowed_roots.is_empty()).then(|| {
+            config
+                .allowed_roots
+                .iter()
+                .filter_map(|root| match std::fs::canonicalize(root) {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        tracing::warn!(root = %root, error = %e, "Ignoring allowed root that can't be resolved");
+                        None
+                    }
+                })
+                .collect()
+        });
+
+        Self {
+            roots,
+            allow_skip_permissions: config.allow_skip_permissions,
+        }
+    }
+
+    /// The canonical form of `dir`, if it exists and sessions may work there.
+    pub fn check_directory(&self, dir: &str) -> Result<PathBuf> {
+        let path = std::fs::canonicalize(dir)
+            .ok()
+            .filter(|p| p.is_dir())
+            .ok_or_else(|| InvalidSessionOptions(format!("Working directory does not exist: {dir}")))?;
+
+        match &self.roots {
+            Some(roots) if !roots.iter().any(|root| path.starts_with(root)) => {
+                Err(PolicyViolation(format!(
+                    "{} is outside agents.security.allowed_roots",
+                    path.display()
+                ))
+                .into())
+            }
+            _ => Ok(path),
+        }
+    }
+
+    /// Refuse to run without permission checks unless the config allows it.
+    pub fn check_permissions(&self, skip: bool, mode: Option<PermissionMode>) -> Result<()> {
+        let bypass = skip || mode == Some(PermissionMode::BypassPermissions);
+        if bypass && !self.allow_skip_permissions {
+            return Err(PolicyViolation(
+                "Skipping permission checks is disabled by agents.security".to_string(),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn policy(roots: &[&Path], allow_skip_permissions: bool) -> SecurityPolicy {
+        SecurityPolicy::new(&SecurityConfig {
+            allowed_roots: roots.iter().map(|r| r.display().to_string()).collect(),
+            allow_skip_permissions,
+        })
+    }
+
+    /// A scratch directory holding `a/sub` and `ab`.
+    fn scratch() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/sub")).unwrap();
+        std::fs::create_dir(dir.path().join("ab")).unwrap();
+        dir
+    }
+
+    fn check(policy: &SecurityPolicy, dir: &Path) -> Result<PathBuf> {
+        policy.check_directory(&dir.display().to_string())
+    }
+
+    fn is_violation(result: Result<PathBuf>) -> bool {
+        result.is_err_and(|e| e.is::<PolicyViolation>())
+    }
+
+    #[test]
+    fn allows_directories_inside_a_root() {
+        let dir = scratch();
+        let root = dir.path().join("a");
+        let policy = policy(&[&root], true);
+
+        let canonical = root.canonicalize().unwrap();
+        assert_eq!(check(&policy, &root).unwrap(), canonical);
+        assert_eq!(check(&policy, &root.join("sub")).unwrap(), canonical.join("sub"));
+        assert_eq!(check(&policy, &root.join("sub/..")).unwrap(), canonical);
+    }
+
+    #[test]
+    fn allows_any_directory_without_roots() {
+        let dir = scratch();
+        let policy = policy(&[], true);
+
+        assert!(check(&policy, &dir.path().join("ab")).is_ok());
+    }
+
+    #[test]
+    fn rejects_sibling_sharing_a_prefix() {
+        let dir = scratch();
+        let policy = policy(&[&dir.path().join("a")], true);
+
+        assert!(is_violation(check(&policy, &dir.path().join("ab"))));
+    }
+
+    #[test]
+    fn rejects_dot_dot_escapes() {
+        let dir = scratch();
+        let policy = policy(&[&dir.path().join("a")], true);
+
+        assert!(is_violation(check(&policy, &dir.path().join("a/.."))));
+        assert!(is_violation(check(&policy, &dir.path().join("a/sub/../../ab"))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_a_root() {
+        let dir = scratch();
+        let root = dir.path().join("a");
+        std::os::unix::fs::symlink(dir.path().join("ab"), root.join("link")).unwrap();
+        let policy = policy(&[&root], true);
+
+        assert!(is_violation(check(&policy, &root.join("link"))));
+    }
+
+    #[test]
+    fn rejects_missing_directories() {
+        let dir = scratch();
+        let policy = policy(&[&dir.path().join("a")], true);
+
+        let missing = check(&policy, &dir.path().join("a/missing"));
+        assert!(missing.is_err_and(|e| e.is::<InvalidSessionOptions>()));
+    }
+
+    #[test]
+    fn ignores_roots_that_do_not_exist() {
+        let dir = scratch();
+        let policy = policy(&[&dir.path().join("missing"), &dir.path().join("a")], true);
+
+        assert!(check(&policy, &dir.path().join("a")).is_ok());
+        assert!(is_violation(check(&policy, &dir.path().join("ab"))));
+    }
+
+    #[test]
+    fn checks_permission_bypass() {
+        let strict = policy(&[], false);
+        assert!(strict.check_permissions(false, None).is_ok());
+        assert!(strict
+            .check_permissions(false, Some(PermissionMode::AcceptEdits))
+            .is_ok());
+        assert!(strict
+            .check_permissions(true, None)
+            .is_err_and(|e| e.is::<PolicyViolation>()));
+        assert!(strict
+            .check_permissions(false, Some(PermissionMode::BypassPermissions))
+            .is_err_and(|e| e.is::<PolicyViolation>()));
+
+        let lenient = policy(&[], true);
+        assert!(lenient.check_permissions(true, None).is_ok());
+        assert!(lenient
+            .check_permissions(false, Some(PermissionMode::BypassPermissions))
+            .is_ok());
+    }
+}
@@ -0,0 +1,153 @@
+use super::InvalidSessionOptions;
+use crate::models::AgentSession;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Returned (via `anyhow`) when a session's branch conflicts with the
+/// repository's current branch. The merge is aborted, leaving the repository
+/// as it was.
+#[derive(Debug)]
+pub struct MergeConflict(pub String);
+
+impl std::fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for MergeConflict {}
+
+/// A git worktree a session runs in: `path` has `branch` checked out, a
+/// branch of the repository at `repo`.
+pub(crate) struct Worktree {
+    pub repo: PathBuf,
+    pub path: PathBuf,
+    pub branch: String,
+}
+
+impl Worktree {
+    /// The worktree `session` runs in, if it was started with one.
+    pub fn of(session: &AgentSession) -> Option<Self> {
+        Some(Self {
+            repo: session.worktree_repo.as_ref()?.into(),
+            path: session.working_directory.as_ref()?.into(),
+            branch: session.worktree_branch.clone()?,
+        })
+    }
+
+    /// The top level of the repository containing `dir`.
+    pub async fn repo_root(dir: &str) -> Result<PathBuf> {
+        match git(Path::new(dir), &["rev-parse", "--show-toplevel"]).await {
+            Ok(top) => Ok(PathBuf::from(top.trim())),
+            Err(_) => Err(InvalidSessionOptions(format!("{dir} is not in a git repository")).into()),
+        }
+    }
+
+    /// Check out a new branch `porter/<session id>` of `repo` at `path`,
+    /// starting from its current commit.
+    pub async fn create(repo: PathBuf, path: PathBuf, session_id: &str) -> Result<Self> {
+        let branch = format!("porter/{session_id}");
+        git(
+            &repo,
+            &["worktree", "add", "-b", &branch, &path.to_string_lossy(), "HEAD"],
+        )
+        .await?;
+
+        Ok(Self { repo, path, branch })
+    }
+
+    /// Everything the branch adds to the repository's current branch,
+    /// including changes not yet committed and new files. Neither checkout's
+    /// index is touched.
+    pub async fn diff(&self) -> Result<String> {
+        let base = git(&self.repo, &["merge-base", "HEAD", &self.branch]).await?;
+        let mut diff = git(&self.path, &["diff", base.trim()]).await?;
+
+        // Untracked files aren't in `git diff`; compare each with nothing
+        let untracked = git(&self.path, &["ls-files", "--others", "--exclude-standard", "-z"]).await?;
+        for file in untracked.split('\0').filter(|f| !f.is_empty()) {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(&self.path)
+                .args(["diff", "--no-index", "--", "/dev/null", file])
+                .output()
+                .await?;
+            // Exits with 1 when the files differ, which they always do
+            if output.status.code() != Some(1) {
+                anyhow::bail!(
+                    "git diff failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            diff.push_str(&String::from_utf8_lossy(&output.stdout));
+        }
+        Ok(diff)
+    }
+
+    /// Commit outstanding changes to the branch with `message`, then merge
+    /// it into the repository's current branch.
+    pub async fn merge(&self, message: &str) -> Result<()> {
+        git(&self.path, &["add", "--all"]).await?;
+        if !git(&self.path, &["status", "--porcelain"]).await?.trim().is_empty() {
+            git(&self.path, &["commit", "-m", message]).await?;
+        }
+
+        if let Err(e) = git(&self.repo, &["merge", "--no-ff", "--no-edit", &self.branch]).await {
+            // Conflicts leave a merge in progress; anything else (a dirty
+            // tree, a missing branch) stops git before it starts one
+            if git(&self.repo, &["rev-parse", "-q", "--verify", "MERGE_HEAD"]).await.is_err() {
+                return Err(e);
+            }
+            let _ = git(&self.repo, &["merge", "--abort"]).await;
+            return Err(MergeConflict(e.to_string()).into());
+        }
+        Ok(())
+    }
+
+    /// Whether the worktree has uncommitted changes, or its branch has
+    /// commits the repository's current branch doesn't.
+    pub async fn has_unmerged_work(&self) -> Result<bool> {
+        // A worktree or branch deleted by hand has nothing left to lose
+        if self.path.is_dir()
+            && !git(&self.path, &["status", "--porcelain"]).await?.trim().is_empty()
+        {
+            return Ok(true);
+        }
+        let branch = format!("refs/heads/{}", self.branch);
+        if git(&self.repo, &["rev-parse", "-q", "--verify", &branch]).await.is_err() {
+            return Ok(false);
+        }
+        let ahead = git(&self.repo, &["rev-list", "--count", &format!("HEAD..{branch}")]).await?;
+        Ok(ahead.trim() != "0")
+    }
+
+    /// Delete the worktree and its branch, merged or not.
+    pub async fn remove(&self) -> Result<()> {
+        let path = self.path.to_string_lossy();
+        if git(&self.repo, &["worktree", "remove", "--force", &path]).await.is_err() {
+            // Already deleted by hand; forget it
+            git(&self.repo, &["worktree", "prune"]).await?;
+        }
+        git(&self.repo, &["branch", "-D", &self.branch]).await?;
+        Ok(())
+    }
+}
+
+/// Run git in `dir`, returning its output.
+async fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -1,6 +1,7 @@
+use crate::models::{PermissionMode, TimeoutMode};
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct PorterConfig {
@@ -9,6 +10,9 @@ pub struct PorterConfig {
     pub integrations: IntegrationsConfig,
     #[serde(default)]
     pub agents: AgentsConfig,
+    /// Absolute path of the file this config was loaded from, if any.
+    #[serde(skip)]
+    pub source_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -73,9 +77,67 @@ pub struct AgentsConfig {
     pub max_concurrent_sessions: usize,
     #[serde(default = "default_model")]
     pub default_model: String,
+    /// Models sessions may request instead of `default_model`. Empty allows
+    /// any model.
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
     /// MCP servers available to Claude agent sessions.
     #[serde(default)]
     pub mcp: HashMap<String, McpServerConfig>,
+    /// Agent backends sessions can run on besides `claude_binary`, which is
+    /// always available as `"claude"`. Picked with a profile's `backend`.
+    #[serde(default)]
+    pub backends: HashMap<String, BackendConfig>,
+    /// Spending limits for agent sessions.
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// Where sessions may work and what they may skip.
+    #[serde(default)]
+    pub security: SecurityConfig,
+    /// When finished sessions are deleted.
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// On startup, resume sessions left `running` by a previous server
+    /// process (via `claude --resume`) instead of marking them failed.
+    #[serde(default)]
+    pub resume_orphaned_sessions: bool,
+    /// Seconds running sessions get to finish when the server shuts down
+    /// before they are killed and marked paused.
+    #[serde(default = "default_shutdown_grace_secs")]
+    pub shutdown_grace_secs: u64,
+    /// Seconds to wait for a run's first output (covers MCP server startup).
+    #[serde(default = "default_startup_timeout_secs")]
+    pub startup_timeout_secs: u64,
+    /// Seconds a run may take before it is killed; see `timeout_mode`.
+    #[serde(default = "default_session_timeout_secs")]
+    pub session_timeout_secs: u64,
+    /// Whether `session_timeout_secs` limits a run's total time or the time
+    /// since its last output.
+    #[serde(default)]
+    pub timeout_mode: TimeoutMode,
+    /// Ask the user (over the API) before Claude uses a tool it hasn't been
+    /// allowed, instead of refusing it. Needs Porter's own MCP server.
+    /// Scheduled and task sessions, which nobody is watching, never ask.
+    #[serde(default)]
+    pub permission_prompts: bool,
+    /// Seconds to wait for an answer before denying the tool call. Session
+    /// timeouts don't count time spent waiting.
+    #[serde(default = "default_permission_timeout_secs")]
+    pub permission_timeout_secs: u64,
+    /// Keep one Claude process per session and write follow-ups to its
+    /// stdin, instead of starting `claude --resume` for each message.
+    #[serde(default)]
+    pub persistent_sessions: bool,
+    /// Seconds a persistent process may wait for a message before it is
+    /// stopped; the next message resumes it in a new process.
+    #[serde(default = "default_persistent_idle_secs")]
+    pub persistent_idle_secs: u64,
+    /// Prompts to run on a schedule, from `[[agents.schedules]]`.
+    #[serde(default)]
+    pub schedules: Vec<ScheduleConfig>,
+    /// Named bundles of session options, from `[agents.profiles.<name>]`.
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileConfig>,
 }
 
 impl Default for AgentsConfig {
@@ -84,11 +146,189 @@ impl Default for AgentsConfig {
             claude_binary: default_claude_binary(),
             max_concurrent_sessions: default_max_sessions(),
             default_model: default_model(),
+            allowed_models: Vec::new(),
             mcp: HashMap::new(),
+            backends: HashMap::new(),
+            budget: BudgetConfig::default(),
+            security: SecurityConfig::default(),
+            retention: RetentionConfig::default(),
+            resume_orphaned_sessions: false,
+            shutdown_grace_secs: default_shutdown_grace_secs(),
+            startup_timeout_secs: default_startup_timeout_secs(),
+            session_timeout_secs: default_session_timeout_secs(),
+            timeout_mode: TimeoutMode::default(),
+            permission_prompts: false,
+            permission_timeout_secs: default_permission_timeout_secs(),
+            persistent_sessions: false,
+            persistent_idle_secs: default_persistent_idle_secs(),
+            schedules: Vec::new(),
+            profiles: HashMap::new(),
+        }
+    }
+}
+
+/// A scheduled agent session defined in config. Synced into the `schedules`
+/// table by name at startup.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleConfig {
+    pub name: String,
+    /// Five-field cron expression in local time, e.g. `"0 8 * * mon-fri"`.
+    pub cron: String,
+    pub prompt: String,
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub dangerously_skip_permissions: bool,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+/// Session defaults selected by name when starting a session. Anything the
+/// request sets explicitly takes precedence.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProfileConfig {
+    pub model: Option<String>,
+    pub append_system_prompt: Option<String>,
+    /// Names of `[agents.mcp]` servers; unset gives the session all of them.
+    pub mcp_servers: Option<Vec<String>>,
+    pub directory: Option<String>,
+    pub permission_mode: Option<PermissionMode>,
+    #[serde(default)]
+    pub dangerously_skip_permissions: bool,
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    #[serde(default)]
+    pub disallowed_tools: Vec<String>,
+    pub max_turns: Option<u32>,
+    /// Seconds a run may take before it is killed.
+    pub timeout_secs: Option<u64>,
+    pub startup_timeout_secs: Option<u64>,
+    pub timeout_mode: Option<TimeoutMode>,
+    /// Give each session its own git worktree and branch of `directory`.
+    #[serde(default)]
+    pub worktree: bool,
+    /// Name of the `[agents.backends]` entry to run on; unset uses Claude.
+    pub backend: Option<String>,
+}
+
+/// An agent backend from `[agents.backends.<name>]`, selected by `type`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackendConfig {
+    /// Another Claude CLI install, e.g. a pinned version.
+    ClaudeCli {
+        #[serde(default = "default_claude_binary")]
+        binary: String,
+    },
+    /// A server with an OpenAI-compatible `/chat/completions` endpoint.
+    /// Chat only: MCP servers and tools aren't available to its sessions.
+    Openai {
+        /// e.g. `http://localhost:11434/v1`
+        base_url: String,
+        /// Sent as a bearer token; `env:VAR` reads it from the environment.
+        api_key: Option<String>,
+        /// Model to request instead of the session's.
+        model: Option<String>,
+    },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Spending limits in USD, from `[agents.budget]`. Unset limits are not enforced.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BudgetConfig {
+    /// Total spend allowed per local calendar day.
+    pub daily_usd: Option<f64>,
+    /// Total spend allowed per local calendar month.
+    pub monthly_usd: Option<f64>,
+    /// Maximum spend for a single session, including follow-ups.
+    pub per_session_usd: Option<f64>,
+    /// Fraction of a limit at which a warning notification is sent.
+    #[serde(default = "default_warn_ratio")]
+    pub warn_ratio: f64,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            daily_usd: None,
+            monthly_usd: None,
+            per_session_usd: None,
+            warn_ratio: default_warn_ratio(),
+        }
+    }
+}
+
+/// Limits on what sessions may be started with, from `[agents.security]`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecurityConfig {
+    /// Directories (and their subdirectories) sessions may work in. Empty
+    /// allows any directory.
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+    /// Whether sessions may skip Claude's permission checks, with
+    /// `dangerously_skip_permissions` or `permission_mode = "bypassPermissions"`.
+    #[serde(default = "default_true")]
+    pub allow_skip_permissions: bool,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            allowed_roots: Vec::new(),
+            allow_skip_permissions: true,
+        }
+    }
+}
+
+/// When finished sessions (with their messages and files) are deleted, from
+/// `[agents.retention]`. Unset limits are not enforced.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionConfig {
+    /// Delete sessions finished more than this many days ago.
+    pub max_age_days: Option<u64>,
+    /// Keep at most this many finished sessions, deleting the oldest.
+    pub max_sessions: Option<usize>,
+    /// Never delete starred sessions (they don't count towards `max_sessions`).
+    #[serde(default = "default_true")]
+    pub keep_starred: bool,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: None,
+            max_sessions: None,
+            keep_starred: true,
         }
     }
 }
 
+fn default_shutdown_grace_secs() -> u64 {
+    30
+}
+
+fn default_persistent_idle_secs() -> u64 {
+    10 * 60
+}
+
+fn default_permission_timeout_secs() -> u64 {
+    5 * 60
+}
+
+fn default_startup_timeout_secs() -> u64 {
+    30
+}
+
+fn default_session_timeout_secs() -> u64 {
+    5 * 60
+}
+
+fn default_warn_ratio() -> f64 {
+    0.8
+}
+
 fn default_claude_binary() -> String {
     "claude".to_string()
 }
@@ -118,7 +358,8 @@ pub struct McpServerConfig {
 impl PorterConfig {
     pub fn load(path: &Path) -> anyhow::Result<Self> {
         let content = std::fs::read_to_string(path)?;
-        let config: PorterConfig = toml::from_str(&content)?;
+        let mut config: PorterConfig = toml::from_str(&content)?;
+        config.source_path = path.canonicalize().ok();
         Ok(config)
     }
 }
@@ -1,8 +1,22 @@
-use sqlx::SqlitePool;
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqliteConnection, SqliteExecutor, SqlitePool};
 
-pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
-    sqlx::raw_sql(
-        "
+/// A forward-only schema change. Versions are applied in ascending order,
+/// each inside its own write-locked transaction, and recorded in
+/// `schema_version`.
+///
+/// Never edit a migration that has shipped — add a new one instead.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: "
         CREATE TABLE IF NOT EXISTS tasks (
             id TEXT PRIMARY KEY,
             title TEXT NOT NULL,
@@ -21,6 +35,9 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
             prompt TEXT NOT NULL,
             status TEXT NOT NULL DEFAULT 'running',
             model TEXT NOT NULL DEFAULT 'opus',
+            claude_session_id TEXT,
+            working_directory TEXT,
+            dangerously_skip_permissions INTEGER NOT NULL DEFAULT 0,
             started_at TEXT NOT NULL,
             completed_at TEXT
         );
@@ -66,47 +83,373 @@ pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
         CREATE INDEX IF NOT EXISTS idx_agent_sessions_status ON agent_sessions(status);
         CREATE INDEX IF NOT EXISTS idx_agent_messages_session ON agent_messages(session_id);
         CREATE INDEX IF NOT EXISTS idx_notifications_read ON notifications(read);
-        ",
+    ",
+    },
+    Migration {
+        version: 2,
+        description: "structured agent transcripts",
+        sql: "
+        ALTER TABLE agent_messages ADD COLUMN content_type TEXT NOT NULL DEFAULT 'text';
+        ALTER TABLE agent_messages ADD COLUMN tool_name TEXT;
+        ALTER TABLE agent_messages ADD COLUMN tool_input TEXT;
+        ALTER TABLE agent_messages ADD COLUMN tool_use_id TEXT;
+        ALTER TABLE agent_messages ADD COLUMN is_error INTEGER NOT NULL DEFAULT 0;
+    ",
+    },
+    Migration {
+        version: 3,
+        description: "agent usage accounting",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN input_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE agent_sessions ADD COLUMN output_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE agent_sessions ADD COLUMN cache_creation_input_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE agent_sessions ADD COLUMN cache_read_input_tokens INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE agent_sessions ADD COLUMN cost_usd REAL NOT NULL DEFAULT 0;
+        ALTER TABLE agent_sessions ADD COLUMN duration_ms INTEGER NOT NULL DEFAULT 0;
+
+        CREATE TABLE agent_usage (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            input_tokens INTEGER NOT NULL,
+            output_tokens INTEGER NOT NULL,
+            cache_creation_input_tokens INTEGER NOT NULL,
+            cache_read_input_tokens INTEGER NOT NULL,
+            cost_usd REAL NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            recorded_at TEXT NOT NULL
+        );
+
+        CREATE INDEX idx_agent_usage_recorded ON agent_usage(recorded_at);
+        CREATE INDEX idx_agent_usage_session ON agent_usage(session_id);
+    ",
+    },
+    Migration {
+        version: 4,
+        description: "agent session queue",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN queue_position INTEGER;
+
+        CREATE INDEX idx_agent_sessions_queue ON agent_sessions(queue_position);
+    ",
+    },
+    Migration {
+        version: 5,
+        description: "scheduled agent sessions",
+        sql: "
+        CREATE TABLE schedules (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            cron TEXT NOT NULL,
+            prompt TEXT NOT NULL,
+            working_directory TEXT,
+            dangerously_skip_permissions INTEGER NOT NULL DEFAULT 0,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            from_config INTEGER NOT NULL DEFAULT 0,
+            next_run_at TEXT,
+            last_run_at TEXT,
+            last_session_id TEXT,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+
+        CREATE INDEX idx_schedules_next_run ON schedules(next_run_at);
+
+        ALTER TABLE agent_sessions ADD COLUMN schedule_id TEXT;
+    ",
+    },
+    Migration {
+        version: 6,
+        description: "task-linked agent sessions and task comments",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN task_id TEXT;
+        ALTER TABLE agent_sessions ADD COLUMN complete_task INTEGER NOT NULL DEFAULT 0;
+
+        CREATE INDEX idx_agent_sessions_task ON agent_sessions(task_id);
+
+        CREATE TABLE task_comments (
+            id TEXT PRIMARY KEY,
+            task_id TEXT NOT NULL,
+            author TEXT NOT NULL,
+            content TEXT NOT NULL,
+            session_id TEXT,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (task_id) REFERENCES tasks(id)
+        );
+
+        CREATE INDEX idx_task_comments_task ON task_comments(task_id);
+    ",
+    },
+    Migration {
+        version: 7,
+        description: "per-session system prompt, tool and MCP overrides",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN append_system_prompt TEXT;
+        ALTER TABLE agent_sessions ADD COLUMN allowed_tools TEXT NOT NULL DEFAULT '[]';
+        ALTER TABLE agent_sessions ADD COLUMN disallowed_tools TEXT NOT NULL DEFAULT '[]';
+        ALTER TABLE agent_sessions ADD COLUMN max_turns INTEGER;
+        ALTER TABLE agent_sessions ADD COLUMN mcp_servers TEXT;
+    ",
+    },
+    Migration {
+        version: 8,
+        description: "agent profiles, permission mode and session timeout",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN profile TEXT;
+        ALTER TABLE agent_sessions ADD COLUMN permission_mode TEXT;
+        ALTER TABLE agent_sessions ADD COLUMN timeout_secs INTEGER;
+    ",
+    },
+    Migration {
+        version: 9,
+        description: "per-session startup timeout and timeout mode",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN startup_timeout_secs INTEGER;
+        ALTER TABLE agent_sessions ADD COLUMN timeout_mode TEXT;
+    ",
+    },
+    Migration {
+        version: 10,
+        description: "permission allow-rules",
+        sql: "
+        CREATE TABLE permission_rules (
+            id TEXT PRIMARY KEY,
+            session_id TEXT,
+            profile TEXT,
+            tool_name TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        CREATE INDEX idx_permission_rules_session ON permission_rules(session_id);
+        CREATE INDEX idx_permission_rules_profile ON permission_rules(profile);
+    ",
+    },
+    Migration {
+        version: 11,
+        description: "forked agent sessions",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN parent_session_id TEXT;
+    ",
+    },
+    Migration {
+        version: 12,
+        description: "agent session worktrees",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN worktree_repo TEXT;
+        ALTER TABLE agent_sessions ADD COLUMN worktree_branch TEXT;
+    ",
+    },
+    Migration {
+        version: 13,
+        description: "starred agent sessions",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN starred INTEGER NOT NULL DEFAULT 0;
+    ",
+    },
+    Migration {
+        version: 14,
+        description: "agent session backends",
+        sql: "
+        ALTER TABLE agent_sessions ADD COLUMN backend TEXT;
+    ",
+    },
+];
+
+/// Schema version this binary expects.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// A migration as reported by [`migration_status`].
+#[derive(Debug, Clone)]
+pub struct MigrationInfo {
+    pub version: i64,
+    pub description: String,
+    /// `None` if the migration is still pending.
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    pub current_version: i64,
+    pub latest_version: i64,
+    pub migrations: Vec<MigrationInfo>,
+}
+
+/// Safe to race with other processes opening the same database (the server
+/// and its `porter mcp` children): each step takes SQLite's write lock before
+/// checking the version, and a step someone else applied first is skipped.
+pub async fn run_migrations(pool: &SqlitePool) -> anyhow::Result<()> {
+    ensure_version_table(pool).await?;
+
+    let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+    if current_version(&mut *tx).await? == 0 && table_exists(&mut *tx, "tasks").await? {
+        // Database predates versioned migrations: bring it up to the
+        // version 1 schema in place and stamp it.
+        upgrade_legacy_schema(&mut tx).await?;
+        record_version(&mut *tx, &MIGRATIONS[0]).await?;
+        tx.commit().await?;
+        tracing::info!("Adopted pre-versioning database as schema version 1");
+    } else {
+        tx.rollback().await?;
+    }
+
+    check_not_newer(current_version(pool).await?)?;
+
+    for migration in MIGRATIONS {
+        let mut tx = pool.begin_with("BEGIN IMMEDIATE").await?;
+        if current_version(&mut *tx).await? >= migration.version {
+            tx.rollback().await?;
+            continue;
+        }
+        sqlx::raw_sql(migration.sql).execute(&mut *tx).await?;
+        record_version(&mut *tx, migration).await?;
+        tx.commit().await?;
+
+        tracing::info!(
+            version = migration.version,
+            "Applied migration: {}",
+            migration.description
+        );
+    }
+
+    tracing::info!(version = latest_version(), "Database migrations completed");
+    Ok(())
+}
+
+/// Report applied and pending migrations without changing anything.
+pub async fn migration_status(pool: &SqlitePool) -> anyhow::Result<MigrationStatus> {
+    let applied: Vec<(i64, String, String)> = if table_exists(pool, "schema_version").await? {
+        sqlx::query("SELECT version, description, applied_at FROM schema_version ORDER BY version")
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|r| (r.get("version"), r.get("description"), r.get("applied_at")))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut migrations: Vec<MigrationInfo> = applied
+        .iter()
+        .map(|(version, description, applied_at)| MigrationInfo {
+            version: *version,
+            description: description.clone(),
+            applied_at: DateTime::parse_from_rfc3339(applied_at)
+                .ok()
+                .map(|d| d.with_timezone(&Utc)),
+        })
+        .collect();
+
+    for migration in MIGRATIONS {
+        if !applied.iter().any(|(v, _, _)| *v == migration.version) {
+            migrations.push(MigrationInfo {
+                version: migration.version,
+                description: migration.description.to_string(),
+                applied_at: None,
+            });
+        }
+    }
+    migrations.sort_by_key(|m| m.version);
+
+    Ok(MigrationStatus {
+        current_version: applied.iter().map(|(v, _, _)| *v).max().unwrap_or(0),
+        latest_version: latest_version(),
+        migrations,
+    })
+}
+
+fn check_not_newer(current: i64) -> anyhow::Result<()> {
+    let latest = latest_version();
+    if current > latest {
+        anyhow::bail!(
+            "Database schema version {current} is newer than this binary supports ({latest}); \
+             upgrade porter before using this database"
+        );
+    }
+    Ok(())
+}
+
+async fn ensure_version_table(pool: &SqlitePool) -> anyhow::Result<()> {
+    sqlx::raw_sql(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
     )
     .execute(pool)
     .await?;
+    Ok(())
+}
+
+async fn current_version<'e>(executor: impl SqliteExecutor<'e>) -> anyhow::Result<i64> {
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_version")
+        .fetch_one(executor)
+        .await?;
+    Ok(row.get("version"))
+}
+
+async fn record_version<'e>(
+    executor: impl SqliteExecutor<'e>,
+    migration: &Migration,
+) -> anyhow::Result<()> {
+    sqlx::query("INSERT INTO schema_version (version, description, applied_at) VALUES (?, ?, ?)")
+        .bind(migration.version)
+        .bind(migration.description)
+        .bind(Utc::now().to_rfc3339())
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+async fn table_exists<'e>(executor: impl SqliteExecutor<'e>, table: &str) -> anyhow::Result<bool> {
+    let row = sqlx::query("SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = ?")
+        .bind(table)
+        .fetch_one(executor)
+        .await?;
+    Ok(row.get::<i64, _>("count") > 0)
+}
+
+/// Bring a database created before `schema_version` existed up to the
+/// version 1 schema. Those databases may be missing columns that were added
+/// ad hoc, so this is written to be idempotent. Runs inside the caller's
+/// transaction so a failure leaves the database as it was.
+async fn upgrade_legacy_schema(conn: &mut SqliteConnection) -> anyhow::Result<()> {
+    sqlx::raw_sql(MIGRATIONS[0].sql).execute(&mut *conn).await?;
 
     // Add integration_id to tasks (old DBs had skill_id instead)
-    add_column_if_missing(pool, "tasks", "integration_id", "TEXT").await?;
+    add_column_if_missing(conn, "tasks", "integration_id", "TEXT").await?;
 
-    // Add columns to agent_sessions (idempotent for existing DBs)
-    add_column_if_missing(pool, "agent_sessions", "claude_session_id", "TEXT").await?;
-    add_column_if_missing(pool, "agent_sessions", "working_directory", "TEXT").await?;
+    add_column_if_missing(conn, "agent_sessions", "claude_session_id", "TEXT").await?;
+    add_column_if_missing(conn, "agent_sessions", "working_directory", "TEXT").await?;
     add_column_if_missing(
-        pool,
+        conn,
         "agent_sessions",
         "dangerously_skip_permissions",
         "INTEGER NOT NULL DEFAULT 0",
     )
     .await?;
 
-    tracing::info!("Database migrations completed");
     Ok(())
 }
 
 async fn add_column_if_missing(
-    pool: &SqlitePool,
+    conn: &mut SqliteConnection,
     table: &str,
     column: &str,
     col_type: &str,
 ) -> anyhow::Result<()> {
-    let sql = format!("ALTER TABLE {table} ADD COLUMN {column} {col_type}");
-    match sqlx::raw_sql(&sql).execute(pool).await {
-        Ok(_) => {
-            tracing::info!("Added column {column} to {table}");
-        }
-        Err(e) => {
-            // "duplicate column name" means it already exists — safe to ignore
-            let msg = e.to_string();
-            if !msg.contains("duplicate column") {
-                return Err(e.into());
-            }
-        }
+    let exists = sqlx::query(&format!("SELECT COUNT(*) AS count FROM pragma_table_info('{table}') WHERE name = ?"))
+        .bind(column)
+        .fetch_one(&mut *conn)
+        .await?
+        .get::<i64, _>("count")
+        > 0;
+
+    if !exists {
+        let sql = format!("ALTER TABLE {table} ADD COLUMN {column} {col_type}");
+        sqlx::raw_sql(&sql).execute(&mut *conn).await?;
+        tracing::info!("Added column {column} to {table}");
     }
     Ok(())
 }
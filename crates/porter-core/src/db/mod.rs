@@ -1,5 +1,40 @@
 mod migrations;
 mod queries;
 
-pub use migrations::run_migrations;
+pub use migrations::{
+    latest_version, migration_status, run_migrations, MigrationInfo, MigrationStatus,
+};
 pub use queries::Database;
+
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+
+/// Open (creating if needed) the SQLite database at `db_path` without
+/// touching its schema.
+pub async fn open_pool(db_path: &str) -> anyhow::Result<SqlitePool> {
+    let db_url = format!("sqlite:{db_path}?mode=rwc");
+    let pool = SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect(&db_url)
+        .await?;
+    Ok(pool)
+}
+
+/// Open the existing SQLite database at `db_path` for reading only, e.g. to
+/// inspect it without migrating.
+pub async fn open_pool_read_only(db_path: &str) -> anyhow::Result<SqlitePool> {
+    let db_url = format!("sqlite:{db_path}?mode=ro");
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(&db_url)
+        .await?;
+    Ok(pool)
+}
+
+/// Open the SQLite database at `db_path` and bring its schema up to date.
+/// Fails if the database was written by a newer version of Porter.
+pub async fn connect(db_path: &str) -> anyhow::Result<Database> {
+    let pool = open_pool(db_path).await?;
+    run_migrations(&pool).await?;
+    Ok(Database::new(pool))
+}
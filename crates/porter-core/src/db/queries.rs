@@ -1,7 +1,8 @@
 use crate::models::*;
-use chrono::Utc;
+use crate::schedules::Cron;
+use chrono::{DateTime, Utc};
 use sqlx::sqlite::SqliteRow;
-use sqlx::{Row, SqlitePool};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
 use uuid::Uuid;
 
 #[derive(Clone, Debug)]
@@ -116,6 +117,11 @@ impl Database {
     }
 
     pub async fn delete_task(&self, id: &str) -> anyhow::Result<bool> {
+        sqlx::query("DELETE FROM task_comments WHERE task_id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
         let result = sqlx::query("DELETE FROM tasks WHERE id = ?")
             .bind(id)
             .execute(&self.pool)
@@ -123,6 +129,47 @@ impl Database {
         Ok(result.rows_affected() > 0)
     }
 
+    pub async fn add_task_comment(&self, comment: &TaskComment) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO task_comments (id, task_id, author, content, session_id, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&comment.id)
+        .bind(&comment.task_id)
+        .bind(&comment.author)
+        .bind(&comment.content)
+        .bind(&comment.session_id)
+        .bind(comment.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list_task_comments(&self, task_id: &str) -> anyhow::Result<Vec<TaskComment>> {
+        let rows = sqlx::query(
+            "SELECT * FROM task_comments WHERE task_id = ? ORDER BY created_at, rowid",
+        )
+        .bind(task_id)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(task_comment_from_row).collect()
+    }
+
+    /// Open (pending or in-progress) tasks with a due date at or before `before`.
+    pub async fn list_open_tasks_due_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Task>> {
+        let rows = sqlx::query(
+            "SELECT * FROM tasks WHERE due_date IS NOT NULL AND due_date <= ?
+             AND status IN ('pending', 'in_progress') ORDER BY due_date ASC",
+        )
+        .bind(before.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(task_from_row).collect()
+    }
+
     pub async fn count_tasks_by_status(&self, status: &str) -> anyhow::Result<i64> {
         let row = sqlx::query("SELECT COUNT(*) as count FROM tasks WHERE status = ?")
             .bind(status)
@@ -133,41 +180,129 @@ impl Database {
 
     // ── Agent Sessions ──
 
+    /// Insert a new session. `Queued` sessions are placed at the back of the
+    /// queue, overriding any `queue_position` given.
     pub async fn create_agent_session(
         &self,
-        prompt: &str,
-        model: &str,
-        working_directory: Option<&str>,
-        dangerously_skip_permissions: bool,
+        mut session: AgentSession,
     ) -> anyhow::Result<AgentSession> {
-        let session = AgentSession {
-            id: Uuid::new_v4().to_string(),
-            prompt: prompt.to_string(),
-            status: AgentStatus::Running,
-            model: model.to_string(),
-            claude_session_id: None,
-            working_directory: working_directory.map(String::from),
-            dangerously_skip_permissions,
-            started_at: Utc::now(),
-            completed_at: None,
-        };
+        session.queue_position = None;
+        if session.status == AgentStatus::Queued {
+            let row = sqlx::query(
+                "SELECT COALESCE(MAX(queue_position), 0) + 1 AS next FROM agent_sessions WHERE status = 'queued'",
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            session.queue_position = Some(row.get("next"));
+        }
+
+        let allowed_tools_json = serde_json::to_string(&session.allowed_tools)?;
+        let disallowed_tools_json = serde_json::to_string(&session.disallowed_tools)?;
+        let mcp_servers_json = session
+            .mcp_servers
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
 
         sqlx::query(
-            "INSERT INTO agent_sessions (id, prompt, status, model, working_directory, dangerously_skip_permissions, started_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO agent_sessions (id, prompt, status, model, claude_session_id, working_directory, dangerously_skip_permissions, queue_position, schedule_id, task_id, complete_task, append_system_prompt, allowed_tools, disallowed_tools, max_turns, mcp_servers, profile, permission_mode, timeout_secs, startup_timeout_secs, timeout_mode, parent_session_id, worktree_repo, worktree_branch, backend, started_at, completed_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&session.id)
         .bind(&session.prompt)
         .bind(session.status.as_str())
         .bind(&session.model)
+        .bind(&session.claude_session_id)
         .bind(&session.working_directory)
         .bind(session.dangerously_skip_permissions)
+        .bind(session.queue_position)
+        .bind(&session.schedule_id)
+        .bind(&session.task_id)
+        .bind(session.complete_task)
+        .bind(&session.append_system_prompt)
+        .bind(&allowed_tools_json)
+        .bind(&disallowed_tools_json)
+        .bind(session.max_turns)
+        .bind(&mcp_servers_json)
+        .bind(&session.profile)
+        .bind(session.permission_mode.map(|m| m.as_str()))
+        .bind(session.timeout_secs.map(|s| s as i64))
+        .bind(session.startup_timeout_secs.map(|s| s as i64))
+        .bind(session.timeout_mode.map(|m| m.as_str()))
+        .bind(&session.parent_session_id)
+        .bind(&session.worktree_repo)
+        .bind(&session.worktree_branch)
+        .bind(&session.backend)
         .bind(session.started_at.to_rfc3339())
+        .bind(session.completed_at.map(|d| d.to_rfc3339()))
         .execute(&self.pool)
         .await?;
 
         Ok(session)
     }
 
+    /// Queued sessions in the order they will start.
+    pub async fn list_queued_sessions(&self) -> anyhow::Result<Vec<AgentSession>> {
+        let rows = sqlx::query(
+            "SELECT * FROM agent_sessions WHERE status = 'queued' ORDER BY queue_position, started_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(agent_session_from_row).collect()
+    }
+
+    /// Move a queued session to `status` `Running`, taking it off the queue.
+    /// Returns the updated session, or `None` if it was no longer queued.
+    pub async fn start_queued_session(&self, id: &str) -> anyhow::Result<Option<AgentSession>> {
+        let result = sqlx::query(
+            "UPDATE agent_sessions SET status = 'running', queue_position = NULL, started_at = ?
+             WHERE id = ? AND status = 'queued'",
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get_agent_session(id).await
+    }
+
+    /// Move a queued session to `position` (1-based, clamped to the queue
+    /// length) and renumber the queue. Returns false if it isn't queued.
+    pub async fn move_queued_session(&self, id: &str, position: usize) -> anyhow::Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut ids: Vec<String> = sqlx::query(
+            "SELECT id FROM agent_sessions WHERE status = 'queued' ORDER BY queue_position, started_at",
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|r| r.get("id"))
+        .collect();
+
+        let Some(current) = ids.iter().position(|i| i == id) else {
+            return Ok(false);
+        };
+        let moved = ids.remove(current);
+        let index = position.saturating_sub(1).min(ids.len());
+        ids.insert(index, moved);
+
+        for (i, id) in ids.iter().enumerate() {
+            sqlx::query("UPDATE agent_sessions SET queue_position = ? WHERE id = ?")
+                .bind(i as i64 + 1)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
     pub async fn get_agent_session(&self, id: &str) -> anyhow::Result<Option<AgentSession>> {
         let row = sqlx::query("SELECT * FROM agent_sessions WHERE id = ?")
             .bind(id)
@@ -212,7 +347,7 @@ impl Database {
         };
 
         let result = sqlx::query(
-            "UPDATE agent_sessions SET status = ?, completed_at = COALESCE(?, completed_at) WHERE id = ?",
+            "UPDATE agent_sessions SET status = ?, completed_at = COALESCE(?, completed_at), queue_position = NULL WHERE id = ?",
         )
         .bind(status.as_str())
         .bind(completed_at)
@@ -229,39 +364,86 @@ impl Database {
         role: &str,
         content: &str,
     ) -> anyhow::Result<AgentMessage> {
-        let msg = AgentMessage {
-            id: Uuid::new_v4().to_string(),
-            session_id: session_id.to_string(),
-            role: role.to_string(),
-            content: content.to_string(),
-            timestamp: Utc::now(),
-        };
+        let msg = AgentMessage::new(session_id, role, "text", content);
+        self.insert_agent_message(&msg).await?;
+        Ok(msg)
+    }
+
+    pub async fn insert_agent_message(&self, msg: &AgentMessage) -> anyhow::Result<()> {
+        let tool_input = msg
+            .tool_input
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
 
         sqlx::query(
-            "INSERT INTO agent_messages (id, session_id, role, content, timestamp) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO agent_messages (id, session_id, role, content, content_type, tool_name, tool_input, tool_use_id, is_error, timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&msg.id)
         .bind(&msg.session_id)
         .bind(&msg.role)
         .bind(&msg.content)
+        .bind(&msg.content_type)
+        .bind(&msg.tool_name)
+        .bind(tool_input)
+        .bind(&msg.tool_use_id)
+        .bind(msg.is_error)
         .bind(msg.timestamp.to_rfc3339())
         .execute(&self.pool)
         .await?;
 
-        Ok(msg)
+        Ok(())
+    }
+
+    /// Copy `from`'s transcript into session `to`, keeping timestamps.
+    pub async fn copy_agent_messages(&self, from: &str, to: &str) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let ids: Vec<String> = sqlx::query(
+            "SELECT id FROM agent_messages WHERE session_id = ? ORDER BY timestamp ASC, rowid ASC",
+        )
+        .bind(from)
+        .fetch_all(&mut *tx)
+        .await?
+        .iter()
+        .map(|r| r.get("id"))
+        .collect();
+
+        for id in &ids {
+            sqlx::query(
+                "INSERT INTO agent_messages (id, session_id, role, content, content_type, tool_name, tool_input, tool_use_id, is_error, timestamp)
+                 SELECT ?, ?, role, content, content_type, tool_name, tool_input, tool_use_id, is_error, timestamp
+                 FROM agent_messages WHERE id = ?",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(to)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
     }
 
     pub async fn delete_agent_session(&self, id: &str) -> anyhow::Result<bool> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query("DELETE FROM agent_messages WHERE session_id = ?")
             .bind(id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM permission_rules WHERE session_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
             .await?;
 
         let result = sqlx::query("DELETE FROM agent_sessions WHERE id = ?")
             .bind(id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
 
+        tx.commit().await?;
         Ok(result.rows_affected() > 0)
     }
 
@@ -280,6 +462,58 @@ impl Database {
         Ok(result.rows_affected() > 0)
     }
 
+    pub async fn set_session_starred(&self, session_id: &str, starred: bool) -> anyhow::Result<bool> {
+        let result = sqlx::query("UPDATE agent_sessions SET starred = ? WHERE id = ?")
+            .bind(starred)
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Forget a session's worktree once it has been removed.
+    pub async fn clear_worktree(&self, session_id: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            "UPDATE agent_sessions SET worktree_repo = NULL, worktree_branch = NULL WHERE id = ?",
+        )
+        .bind(session_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Whether a session other than `session_id` uses `claude_session_id`.
+    pub async fn claude_session_shared(
+        &self,
+        session_id: &str,
+        claude_session_id: &str,
+    ) -> anyhow::Result<bool> {
+        let row = sqlx::query(
+            "SELECT EXISTS(SELECT 1 FROM agent_sessions WHERE claude_session_id = ? AND id != ?) AS shared",
+        )
+        .bind(claude_session_id)
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get("shared"))
+    }
+
+    /// Whether a session other than `session_id` works in `working_directory`.
+    pub async fn working_directory_shared(
+        &self,
+        session_id: &str,
+        working_directory: &str,
+    ) -> anyhow::Result<bool> {
+        let row = sqlx::query(
+            "SELECT EXISTS(SELECT 1 FROM agent_sessions WHERE working_directory = ? AND id != ?) AS shared",
+        )
+        .bind(working_directory)
+        .bind(session_id)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.get("shared"))
+    }
+
     pub async fn set_working_directory(
         &self,
         session_id: &str,
@@ -295,12 +529,88 @@ impl Database {
         Ok(result.rows_affected() > 0)
     }
 
+    /// Record the usage of one Claude run and add it to the session's totals.
+    pub async fn record_agent_usage(
+        &self,
+        session_id: &str,
+        usage: &TokenUsage,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO agent_usage (session_id, input_tokens, output_tokens, cache_creation_input_tokens, cache_read_input_tokens, cost_usd, duration_ms, recorded_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(session_id)
+        .bind(usage.input_tokens)
+        .bind(usage.output_tokens)
+        .bind(usage.cache_creation_input_tokens)
+        .bind(usage.cache_read_input_tokens)
+        .bind(usage.cost_usd)
+        .bind(usage.duration_ms)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE agent_sessions SET
+                input_tokens = input_tokens + ?,
+                output_tokens = output_tokens + ?,
+                cache_creation_input_tokens = cache_creation_input_tokens + ?,
+                cache_read_input_tokens = cache_read_input_tokens + ?,
+                cost_usd = cost_usd + ?,
+                duration_ms = duration_ms + ?
+             WHERE id = ?",
+        )
+        .bind(usage.input_tokens)
+        .bind(usage.output_tokens)
+        .bind(usage.cache_creation_input_tokens)
+        .bind(usage.cache_read_input_tokens)
+        .bind(usage.cost_usd)
+        .bind(usage.duration_ms)
+        .bind(session_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Sum agent usage recorded at or after `since` (all time if `None`).
+    pub async fn agent_usage_summary(
+        &self,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<UsageSummary> {
+        let row = sqlx::query(
+            "SELECT COUNT(DISTINCT session_id) AS sessions,
+                    COUNT(*) AS runs,
+                    COALESCE(SUM(input_tokens), 0) AS input_tokens,
+                    COALESCE(SUM(output_tokens), 0) AS output_tokens,
+                    COALESCE(SUM(cache_creation_input_tokens), 0) AS cache_creation_input_tokens,
+                    COALESCE(SUM(cache_read_input_tokens), 0) AS cache_read_input_tokens,
+                    COALESCE(SUM(cost_usd), 0.0) AS cost_usd,
+                    COALESCE(SUM(duration_ms), 0) AS duration_ms
+             FROM agent_usage WHERE (? IS NULL OR recorded_at >= ?)",
+        )
+        .bind(since.map(|d| d.to_rfc3339()))
+        .bind(since.map(|d| d.to_rfc3339()))
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(UsageSummary {
+            since,
+            sessions: row.get("sessions"),
+            runs: row.get("runs"),
+            usage: usage_from_row(&row),
+        })
+    }
+
     pub async fn get_agent_messages(
         &self,
         session_id: &str,
     ) -> anyhow::Result<Vec<AgentMessage>> {
         let rows =
-            sqlx::query("SELECT * FROM agent_messages WHERE session_id = ? ORDER BY timestamp ASC")
+            sqlx::query("SELECT * FROM agent_messages WHERE session_id = ? ORDER BY timestamp ASC, rowid ASC")
                 .bind(session_id)
                 .fetch_all(&self.pool)
                 .await?;
@@ -308,8 +618,6 @@ impl Database {
         rows.iter().map(agent_message_from_row).collect()
     }
 
-    // ── Notifications ──
-
     // ── Integration State ──
 
     pub async fn get_integration_state(
@@ -373,14 +681,7 @@ impl Database {
         message: &str,
         integration_id: Option<&str>,
     ) -> anyhow::Result<Notification> {
-        let notification = Notification {
-            id: Uuid::new_v4().to_string(),
-            notification_type: notification_type.to_string(),
-            message: message.to_string(),
-            read: false,
-            integration_id: integration_id.map(String::from),
-            created_at: Utc::now(),
-        };
+        let notification = Notification::new(notification_type, message, integration_id);
 
         sqlx::query(
             "INSERT INTO notifications (id, notification_type, message, read, integration_id, created_at) VALUES (?, ?, ?, 0, ?, ?)",
@@ -395,6 +696,295 @@ impl Database {
 
         Ok(notification)
     }
+
+    pub async fn list_notifications(
+        &self,
+        filter: &NotificationFilter,
+    ) -> anyhow::Result<Vec<Notification>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM notifications WHERE 1 = 1");
+        if let Some(read) = filter.read {
+            query.push(" AND read = ").push_bind(read);
+        }
+        if let Some(ref integration_id) = filter.integration_id {
+            query.push(" AND integration_id = ").push_bind(integration_id);
+        }
+        if let Some(ref notification_type) = filter.notification_type {
+            query
+                .push(" AND notification_type = ")
+                .push_bind(notification_type);
+        }
+        if let Some(since) = filter.since {
+            query.push(" AND created_at >= ").push_bind(since.to_rfc3339());
+        }
+        query
+            .push(" ORDER BY created_at DESC LIMIT ")
+            .push_bind(filter.limit.unwrap_or(50))
+            .push(" OFFSET ")
+            .push_bind(filter.offset.unwrap_or(0));
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        rows.iter().map(notification_from_row).collect()
+    }
+
+    pub async fn get_notification(&self, id: &str) -> anyhow::Result<Option<Notification>> {
+        let row = sqlx::query("SELECT * FROM notifications WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match row {
+            Some(row) => Ok(Some(notification_from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn mark_notification_read(&self, id: &str) -> anyhow::Result<Option<Notification>> {
+        sqlx::query("UPDATE notifications SET read = 1 WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        self.get_notification(id).await
+    }
+
+    /// Mark every unread notification (optionally only those from one
+    /// integration) as read. Returns the IDs that changed.
+    pub async fn mark_all_notifications_read(
+        &self,
+        integration_id: Option<&str>,
+    ) -> anyhow::Result<Vec<String>> {
+        let rows = sqlx::query(
+            "UPDATE notifications SET read = 1
+             WHERE read = 0 AND (? IS NULL OR integration_id = ?)
+             RETURNING id",
+        )
+        .bind(integration_id)
+        .bind(integration_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(|r| r.get("id")).collect())
+    }
+
+    pub async fn delete_notification(&self, id: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM notifications WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ── Schedules ──
+
+    /// Create a schedule. Fails with a [`CronError`](crate::schedules::CronError) if the cron expression
+    /// is invalid.
+    pub async fn create_schedule(
+        &self,
+        input: CreateSchedule,
+        from_config: bool,
+    ) -> anyhow::Result<Schedule> {
+        let cron: Cron = input.cron.parse()?;
+        let mut schedule = Schedule {
+            from_config,
+            ..Schedule::new(input)
+        };
+        schedule.cron = cron.to_string();
+        if schedule.enabled {
+            schedule.next_run_at = cron.next_after(Utc::now());
+        }
+
+        sqlx::query(
+            "INSERT INTO schedules (id, name, cron, prompt, working_directory, dangerously_skip_permissions, enabled, from_config, next_run_at, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&schedule.id)
+        .bind(&schedule.name)
+        .bind(&schedule.cron)
+        .bind(&schedule.prompt)
+        .bind(&schedule.working_directory)
+        .bind(schedule.dangerously_skip_permissions)
+        .bind(schedule.enabled)
+        .bind(schedule.from_config)
+        .bind(schedule.next_run_at.map(|d| d.to_rfc3339()))
+        .bind(schedule.created_at.to_rfc3339())
+        .bind(schedule.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(schedule)
+    }
+
+    pub async fn get_schedule(&self, id: &str) -> anyhow::Result<Option<Schedule>> {
+        let row = sqlx::query("SELECT * FROM schedules WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(schedule_from_row).transpose()
+    }
+
+    pub async fn get_schedule_by_name(&self, name: &str) -> anyhow::Result<Option<Schedule>> {
+        let row = sqlx::query("SELECT * FROM schedules WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(schedule_from_row).transpose()
+    }
+
+    pub async fn list_schedules(&self) -> anyhow::Result<Vec<Schedule>> {
+        let rows = sqlx::query("SELECT * FROM schedules ORDER BY name")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(schedule_from_row).collect()
+    }
+
+    /// Enabled schedules whose next run is at or before `now`.
+    pub async fn list_due_schedules(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<Schedule>> {
+        let rows = sqlx::query(
+            "SELECT * FROM schedules WHERE enabled = 1 AND next_run_at IS NOT NULL AND next_run_at <= ?
+             ORDER BY next_run_at",
+        )
+        .bind(now.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(schedule_from_row).collect()
+    }
+
+    /// Update a schedule. The next run is recomputed from now when the cron
+    /// expression changes or the schedule is re-enabled. Fails with a
+    /// [`CronError`](crate::schedules::CronError) if the new cron expression is invalid.
+    pub async fn update_schedule(
+        &self,
+        id: &str,
+        input: UpdateSchedule,
+    ) -> anyhow::Result<Option<Schedule>> {
+        let Some(mut schedule) = self.get_schedule(id).await? else {
+            return Ok(None);
+        };
+
+        let mut reschedule = false;
+        if let Some(cron) = input.cron {
+            let cron = cron.parse::<Cron>()?.to_string();
+            reschedule |= cron != schedule.cron;
+            schedule.cron = cron;
+        }
+        if let Some(enabled) = input.enabled {
+            reschedule |= enabled && !schedule.enabled;
+            schedule.enabled = enabled;
+        }
+        if let Some(name) = input.name {
+            schedule.name = name;
+        }
+        if let Some(prompt) = input.prompt {
+            schedule.prompt = prompt;
+        }
+        if input.directory.is_some() {
+            schedule.working_directory = input.directory;
+        }
+        if let Some(skip) = input.dangerously_skip_permissions {
+            schedule.dangerously_skip_permissions = skip;
+        }
+
+        if !schedule.enabled {
+            schedule.next_run_at = None;
+        } else if reschedule || schedule.next_run_at.is_none() {
+            schedule.next_run_at = schedule.cron.parse::<Cron>()?.next_after(Utc::now());
+        }
+        schedule.updated_at = Utc::now();
+
+        sqlx::query(
+            "UPDATE schedules SET name = ?, cron = ?, prompt = ?, working_directory = ?, dangerously_skip_permissions = ?, enabled = ?, next_run_at = ?, updated_at = ?
+             WHERE id = ?",
+        )
+        .bind(&schedule.name)
+        .bind(&schedule.cron)
+        .bind(&schedule.prompt)
+        .bind(&schedule.working_directory)
+        .bind(schedule.dangerously_skip_permissions)
+        .bind(schedule.enabled)
+        .bind(schedule.next_run_at.map(|d| d.to_rfc3339()))
+        .bind(schedule.updated_at.to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Some(schedule))
+    }
+
+    /// Record that a schedule fired at `ran_at` (starting `session_id`, if
+    /// the session could be started) and when it should fire next.
+    pub async fn record_schedule_run(
+        &self,
+        id: &str,
+        session_id: Option<&str>,
+        ran_at: DateTime<Utc>,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE schedules SET last_run_at = ?, last_session_id = COALESCE(?, last_session_id), next_run_at = ?
+             WHERE id = ?",
+        )
+        .bind(ran_at.to_rfc3339())
+        .bind(session_id)
+        .bind(next_run_at.map(|d| d.to_rfc3339()))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_schedule(&self, id: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM schedules WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ── Permission Rules ──
+
+    pub async fn add_permission_rule(&self, rule: &PermissionRule) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO permission_rules (id, session_id, profile, tool_name, created_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&rule.id)
+        .bind(&rule.session_id)
+        .bind(&rule.profile)
+        .bind(&rule.tool_name)
+        .bind(rule.created_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list_permission_rules(&self) -> anyhow::Result<Vec<PermissionRule>> {
+        let rows = sqlx::query("SELECT * FROM permission_rules ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(permission_rule_from_row).collect()
+    }
+
+    /// Rules that apply to a session: its own, plus its profile's.
+    pub async fn list_session_permission_rules(
+        &self,
+        session_id: &str,
+        profile: Option<&str>,
+    ) -> anyhow::Result<Vec<PermissionRule>> {
+        let rows = sqlx::query(
+            "SELECT * FROM permission_rules WHERE session_id = ? OR (profile IS NOT NULL AND profile = ?) ORDER BY created_at",
+        )
+        .bind(session_id)
+        .bind(profile)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(permission_rule_from_row).collect()
+    }
+
+    pub async fn delete_permission_rule(&self, id: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM permission_rules WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
 }
 
 // ── Row mapping helpers ──
@@ -429,12 +1019,43 @@ fn task_from_row(row: &SqliteRow) -> anyhow::Result<Task> {
     })
 }
 
+fn task_comment_from_row(row: &SqliteRow) -> anyhow::Result<TaskComment> {
+    let created_at: String = row.get("created_at");
+
+    Ok(TaskComment {
+        id: row.get("id"),
+        task_id: row.get("task_id"),
+        author: row.get("author"),
+        content: row.get("content"),
+        session_id: row.get("session_id"),
+        created_at: chrono::DateTime::parse_from_rfc3339(&created_at)?
+            .with_timezone(&Utc),
+    })
+}
+
+fn permission_rule_from_row(row: &SqliteRow) -> anyhow::Result<PermissionRule> {
+    let created_at: String = row.get("created_at");
+
+    Ok(PermissionRule {
+        id: row.get("id"),
+        session_id: row.get("session_id"),
+        profile: row.get("profile"),
+        tool_name: row.get("tool_name"),
+        created_at: chrono::DateTime::parse_from_rfc3339(&created_at)?
+            .with_timezone(&Utc),
+    })
+}
+
 fn agent_session_from_row(row: &SqliteRow) -> anyhow::Result<AgentSession> {
     let started_at: String = row.get("started_at");
     let completed_at: Option<String> = row.get("completed_at");
     let status_str: String = row.get("status");
 
     let skip_perms: bool = row.try_get("dangerously_skip_permissions").unwrap_or(false);
+    let json_list = |column: &str| -> Option<Vec<String>> {
+        let raw: Option<String> = row.try_get(column).unwrap_or(None);
+        raw.and_then(|s| serde_json::from_str(&s).ok())
+    };
 
     Ok(AgentSession {
         id: row.get("id"),
@@ -444,6 +1065,38 @@ fn agent_session_from_row(row: &SqliteRow) -> anyhow::Result<AgentSession> {
         claude_session_id: row.get("claude_session_id"),
         working_directory: row.get("working_directory"),
         dangerously_skip_permissions: skip_perms,
+        usage: usage_from_row(row),
+        queue_position: row.try_get("queue_position").unwrap_or(None),
+        schedule_id: row.try_get("schedule_id").unwrap_or(None),
+        task_id: row.try_get("task_id").unwrap_or(None),
+        complete_task: row.try_get("complete_task").unwrap_or(false),
+        append_system_prompt: row.try_get("append_system_prompt").unwrap_or(None),
+        allowed_tools: json_list("allowed_tools").unwrap_or_default(),
+        disallowed_tools: json_list("disallowed_tools").unwrap_or_default(),
+        max_turns: row.try_get("max_turns").unwrap_or(None),
+        mcp_servers: json_list("mcp_servers"),
+        profile: row.try_get("profile").unwrap_or(None),
+        permission_mode: row
+            .try_get::<Option<String>, _>("permission_mode")
+            .unwrap_or(None)
+            .and_then(|m| PermissionMode::from_str(&m)),
+        timeout_secs: row
+            .try_get::<Option<i64>, _>("timeout_secs")
+            .unwrap_or(None)
+            .map(|s| s as u64),
+        startup_timeout_secs: row
+            .try_get::<Option<i64>, _>("startup_timeout_secs")
+            .unwrap_or(None)
+            .map(|s| s as u64),
+        timeout_mode: row
+            .try_get::<Option<String>, _>("timeout_mode")
+            .unwrap_or(None)
+            .and_then(|m| TimeoutMode::from_str(&m)),
+        parent_session_id: row.try_get("parent_session_id").unwrap_or(None),
+        worktree_repo: row.try_get("worktree_repo").unwrap_or(None),
+        worktree_branch: row.try_get("worktree_branch").unwrap_or(None),
+        starred: row.try_get("starred").unwrap_or(false),
+        backend: row.try_get("backend").unwrap_or(None),
         started_at: chrono::DateTime::parse_from_rfc3339(&started_at)?
             .with_timezone(&Utc),
         completed_at: completed_at
@@ -452,15 +1105,77 @@ fn agent_session_from_row(row: &SqliteRow) -> anyhow::Result<AgentSession> {
     })
 }
 
+fn usage_from_row(row: &SqliteRow) -> TokenUsage {
+    TokenUsage {
+        input_tokens: row.try_get("input_tokens").unwrap_or(0),
+        output_tokens: row.try_get("output_tokens").unwrap_or(0),
+        cache_creation_input_tokens: row.try_get("cache_creation_input_tokens").unwrap_or(0),
+        cache_read_input_tokens: row.try_get("cache_read_input_tokens").unwrap_or(0),
+        cost_usd: row.try_get("cost_usd").unwrap_or(0.0),
+        duration_ms: row.try_get("duration_ms").unwrap_or(0),
+    }
+}
+
 fn agent_message_from_row(row: &SqliteRow) -> anyhow::Result<AgentMessage> {
     let timestamp: String = row.get("timestamp");
 
+    let tool_input: Option<String> = row.get("tool_input");
+
     Ok(AgentMessage {
         id: row.get("id"),
         session_id: row.get("session_id"),
         role: row.get("role"),
         content: row.get("content"),
+        content_type: row.get("content_type"),
+        tool_name: row.get("tool_name"),
+        tool_input: tool_input.and_then(|t| serde_json::from_str(&t).ok()),
+        tool_use_id: row.get("tool_use_id"),
+        is_error: row.get("is_error"),
         timestamp: chrono::DateTime::parse_from_rfc3339(&timestamp)?
             .with_timezone(&Utc),
     })
 }
+
+fn notification_from_row(row: &SqliteRow) -> anyhow::Result<Notification> {
+    let created_at: String = row.get("created_at");
+
+    Ok(Notification {
+        id: row.get("id"),
+        notification_type: row.get("notification_type"),
+        message: row.get("message"),
+        read: row.get("read"),
+        integration_id: row.get("integration_id"),
+        created_at: chrono::DateTime::parse_from_rfc3339(&created_at)?
+            .with_timezone(&Utc),
+    })
+}
+
+fn schedule_from_row(row: &SqliteRow) -> anyhow::Result<Schedule> {
+    let parse = |column: &str| -> anyhow::Result<Option<DateTime<Utc>>> {
+        let value: Option<String> = row.get(column);
+        Ok(value
+            .map(|v| chrono::DateTime::parse_from_rfc3339(&v))
+            .transpose()?
+            .map(|d| d.with_timezone(&Utc)))
+    };
+    let created_at: String = row.get("created_at");
+    let updated_at: String = row.get("updated_at");
+
+    Ok(Schedule {
+        id: row.get("id"),
+        name: row.get("name"),
+        cron: row.get("cron"),
+        prompt: row.get("prompt"),
+        working_directory: row.get("working_directory"),
+        dangerously_skip_permissions: row.get("dangerously_skip_permissions"),
+        enabled: row.get("enabled"),
+        from_config: row.get("from_config"),
+        next_run_at: parse("next_run_at")?,
+        last_run_at: parse("last_run_at")?,
+        last_session_id: row.get("last_session_id"),
+        created_at: chrono::DateTime::parse_from_rfc3339(&created_at)?
+            .with_timezone(&Utc),
+        updated_at: chrono::DateTime::parse_from_rfc3339(&updated_at)?
+            .with_timezone(&Utc),
+    })
+}
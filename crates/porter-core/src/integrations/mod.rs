@@ -1,3 +1,5 @@
+mod schema;
+
 use crate::db::Database;
 use crate::models::Notification;
 use anyhow::Result;
@@ -37,6 +39,19 @@ pub struct Capability {
     pub parameters: serde_json::Value,
 }
 
+impl Capability {
+    /// Check `params` against this capability's JSON schema. Returns one
+    /// message per violation on failure.
+    pub fn validate_params(&self, params: &serde_json::Value) -> Result<(), Vec<String>> {
+        let errors = schema::validate(&self.parameters, params);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
 /// Core trait for built-in integrations that need background processing
 /// or tight coupling with Porter (e.g. tasks, notifications).
 ///
@@ -57,6 +72,8 @@ pub trait Integration: Send + Sync {
     async fn handle(&self, action: Action) -> Result<ActionResult>;
 
     /// Background tick - called periodically for polling-based integrations.
+    /// Returned notifications are stored and broadcast; ones the integration
+    /// already stored itself are only broadcast.
     async fn tick(&self) -> Result<Vec<Notification>>;
 
     /// Handle an inbound webhook request. Integrations that support push
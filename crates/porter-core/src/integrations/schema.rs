@@ -0,0 +1,316 @@
+//! Minimal JSON Schema validation for capability parameters.
+//!
+//! Supports the subset of keywords integrations actually use: `type`, `enum`,
+//! `required`, `properties`, `additionalProperties` (boolean or schema),
+//! `items`, `minimum`/`maximum`, `minLength`/`maxLength` and
+//! `minItems`/`maxItems`. Unknown keywords are ignored.
+
+use serde_json::Value;
+
+/// Validate `value` against `schema`, returning a human-readable message for
+/// every violation found. An empty vec means the value is valid.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(schema, value, "params", &mut errors);
+    errors
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` / `{}`-like schemas accept anything
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => vec![],
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, value)) {
+            errors.push(format!(
+                "{path}: expected {}, got {}",
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            // Further checks would only produce noise
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+            errors.push(format!("{path}: must be one of {}", options.join(", ")));
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !map.contains_key(key) {
+                        errors.push(format!("{path}: missing required property '{key}'"));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            for (key, child) in map {
+                let child_path = format!("{path}.{key}");
+                match properties.and_then(|p| p.get(key)) {
+                    Some(child_schema) => validate_at(child_schema, child, &child_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{path}: unexpected property '{key}'"));
+                        }
+                        Some(extra @ Value::Object(_)) => {
+                            validate_at(extra, child, &child_path, errors);
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_len(schema, "minItems", "maxItems", items.len(), "items", path, errors);
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{path}[{i}]"), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count();
+            check_len(schema, "minLength", "maxLength", len, "characters", path, errors);
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64()) {
+                if n < min {
+                    errors.push(format!("{path}: must be >= {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(|m| m.as_f64()) {
+                if n > max {
+                    errors.push(format!("{path}: must be <= {max}"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_len(
+    schema: &serde_json::Map<String, Value>,
+    min_key: &str,
+    max_key: &str,
+    len: usize,
+    unit: &str,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(min) = schema.get(min_key).and_then(|m| m.as_u64()) {
+        if (len as u64) < min {
+            errors.push(format!("{path}: must have at least {min} {unit}"));
+        }
+    }
+    if let Some(max) = schema.get(max_key).and_then(|m| m.as_u64()) {
+        if (len as u64) > max {
+            errors.push(format!("{path}: must have at most {max} {unit}"));
+        }
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn task_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["title"],
+            "additionalProperties": false,
+            "properties": {
+                "title": { "type": "string", "minLength": 1, "maxLength": 10 },
+                "priority": { "type": "string", "enum": ["low", "medium", "high"] },
+                "estimate": { "type": "number", "minimum": 0, "maximum": 40 },
+                "tags": {
+                    "type": "array",
+                    "maxItems": 2,
+                    "items": { "type": "string" }
+                },
+                "owner": {
+                    "type": "object",
+                    "required": ["name"],
+                    "properties": {
+                        "name": { "type": "string" },
+                        "id": { "type": ["integer", "null"] }
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn accepts_valid_params() {
+        let params = json!({
+            "title": "Taxes",
+            "priority": "high",
+            "estimate": 2.5,
+            "tags": ["home"],
+            "owner": { "name": "Sam", "id": null }
+        });
+        assert!(validate(&task_schema(), &params).is_empty());
+        assert!(validate(&task_schema(), &json!({ "title": "Taxes" })).is_empty());
+    }
+
+    #[test]
+    fn accepts_anything_without_constraints() {
+        assert!(validate(&json!(true), &json!([1, "two"])).is_empty());
+        assert!(validate(&json!({}), &json!({ "any": "thing" })).is_empty());
+        assert!(validate(&json!({ "x-unknown": 1 }), &json!(null)).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_required_properties() {
+        assert_eq!(
+            validate(&task_schema(), &json!({})),
+            ["params: missing required property 'title'"]
+        );
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "Taxes", "owner": {} })),
+            ["params.owner: missing required property 'name'"]
+        );
+    }
+
+    #[test]
+    fn reports_type_mismatches() {
+        assert_eq!(
+            validate(&task_schema(), &json!("Taxes")),
+            ["params: expected object, got string"]
+        );
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": 5 })),
+            ["params.title: expected string, got integer"]
+        );
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "Taxes", "estimate": "two" })),
+            ["params.estimate: expected number, got string"]
+        );
+        let params = json!({ "title": "Taxes", "owner": { "name": "Sam", "id": 1.5 } });
+        assert_eq!(
+            validate(&task_schema(), &params),
+            ["params.owner.id: expected integer or null, got number"]
+        );
+    }
+
+    #[test]
+    fn stops_at_a_type_mismatch() {
+        // A string isn't also checked against the enum
+        let schema = json!({ "type": "string", "enum": ["a"] });
+        assert_eq!(validate(&schema, &json!(1)), ["params: expected string, got integer"]);
+    }
+
+    #[test]
+    fn reports_values_outside_enum() {
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "Taxes", "priority": "urgent" })),
+            [r#"params.priority: must be one of "low", "medium", "high""#]
+        );
+        let schema = json!({ "enum": [1, null] });
+        assert!(validate(&schema, &json!(null)).is_empty());
+        assert_eq!(validate(&schema, &json!(2)), ["params: must be one of 1, null"]);
+    }
+
+    #[test]
+    fn checks_nested_arrays() {
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "Taxes", "tags": ["a", 2, "c"] })),
+            [
+                "params.tags: must have at most 2 items",
+                "params.tags[1]: expected string, got integer",
+            ]
+        );
+        let schema = json!({ "type": "array", "minItems": 1 });
+        assert_eq!(validate(&schema, &json!([])), ["params: must have at least 1 items"]);
+    }
+
+    #[test]
+    fn checks_lengths_and_bounds() {
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "" })),
+            ["params.title: must have at least 1 characters"]
+        );
+        // Lengths count characters, not bytes
+        assert!(validate(&task_schema(), &json!({ "title": "ééééééééé" })).is_empty());
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "Much too long" })),
+            ["params.title: must have at most 10 characters"]
+        );
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "Taxes", "estimate": -1 })),
+            ["params.estimate: must be >= 0"]
+        );
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "Taxes", "estimate": 40.5 })),
+            ["params.estimate: must be <= 40"]
+        );
+    }
+
+    #[test]
+    fn checks_additional_properties() {
+        assert_eq!(
+            validate(&task_schema(), &json!({ "title": "Taxes", "colour": "red" })),
+            ["params: unexpected property 'colour'"]
+        );
+
+        let schema = json!({ "type": "object", "additionalProperties": { "type": "integer" } });
+        assert_eq!(
+            validate(&schema, &json!({ "a": 1, "b": "2" })),
+            ["params.b: expected integer, got string"]
+        );
+        // Allowed unless forbidden
+        assert!(validate(&json!({ "type": "object" }), &json!({ "a": 1 })).is_empty());
+    }
+
+    #[test]
+    fn reports_every_violation() {
+        let params = json!({ "priority": "urgent", "tags": "home", "colour": "red" });
+        assert_eq!(
+            validate(&task_schema(), &params),
+            [
+                "params: missing required property 'title'",
+                "params: unexpected property 'colour'",
+                r#"params.priority: must be one of "low", "medium", "high""#,
+                "params.tags: expected array, got string",
+            ]
+        );
+    }
+}
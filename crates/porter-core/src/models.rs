@@ -37,6 +37,7 @@ impl TaskStatus {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "pending" => Some(Self::Pending),
@@ -67,6 +68,7 @@ impl TaskPriority {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "low" => Some(Self::Low),
@@ -97,6 +99,24 @@ pub struct UpdateTask {
     pub due_date: Option<DateTime<Utc>>,
 }
 
+/// A note on a task, left by the user or by an agent session working on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskComment {
+    pub id: String,
+    pub task_id: String,
+    /// "user" or "agent".
+    pub author: String,
+    pub content: String,
+    /// The agent session that wrote this comment, if any.
+    pub session_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTaskComment {
+    pub content: String,
+}
+
 // ── Agent Sessions ──
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,13 +128,96 @@ pub struct AgentSession {
     pub claude_session_id: Option<String>,
     pub working_directory: Option<String>,
     pub dangerously_skip_permissions: bool,
+    /// Totals across every run of this session (initial prompt and follow-ups).
+    #[serde(default)]
+    pub usage: TokenUsage,
+    /// Place in the start queue while `Queued` (lower starts first).
+    #[serde(default)]
+    pub queue_position: Option<i64>,
+    /// The schedule that started this session, if any.
+    #[serde(default)]
+    pub schedule_id: Option<String>,
+    /// The task this session is working on, if any.
+    #[serde(default)]
+    pub task_id: Option<String>,
+    /// Mark the linked task completed when a run succeeds (otherwise only a
+    /// comment is added).
+    #[serde(default)]
+    pub complete_task: bool,
+    /// Extra instructions appended to Claude's system prompt.
+    #[serde(default)]
+    pub append_system_prompt: Option<String>,
+    /// Tools Claude may use without asking (`--allowedTools`).
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    /// Tools Claude may not use (`--disallowedTools`).
+    #[serde(default)]
+    pub disallowed_tools: Vec<String>,
+    /// Cap on agentic turns per run.
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+    /// Configured MCP servers this session gets; `None` means all of them.
+    #[serde(default)]
+    pub mcp_servers: Option<Vec<String>>,
+    /// The `[agents.profiles]` entry the session was started with.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Claude's `--permission-mode`; `None` uses the CLI default.
+    #[serde(default)]
+    pub permission_mode: Option<PermissionMode>,
+    /// Seconds a run may take before it is killed, if not the default.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Seconds to wait for a run's first output, if not the default.
+    #[serde(default)]
+    pub startup_timeout_secs: Option<u64>,
+    /// How `timeout_secs` is measured, if not the default.
+    #[serde(default)]
+    pub timeout_mode: Option<TimeoutMode>,
+    /// The session this one was forked from (which may since have been deleted).
+    #[serde(default)]
+    pub parent_session_id: Option<String>,
+    /// Repository whose worktree `working_directory` is, if the session was
+    /// started with one.
+    #[serde(default)]
+    pub worktree_repo: Option<String>,
+    /// The session's branch, checked out in its worktree.
+    #[serde(default)]
+    pub worktree_branch: Option<String>,
+    /// Kept by `[agents.retention]` when `keep_starred` is set.
+    #[serde(default)]
+    pub starred: bool,
+    /// The `[agents.backends]` entry it runs on; `None` is Claude.
+    #[serde(default)]
+    pub backend: Option<String>,
     pub started_at: DateTime<Utc>,
     pub completed_at: Option<DateTime<Utc>>,
 }
 
+/// Token, cost and timing figures reported by Claude's `result` event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_creation_input_tokens: i64,
+    pub cache_read_input_tokens: i64,
+    pub cost_usd: f64,
+    pub duration_ms: i64,
+}
+
+/// Aggregate usage across agent runs recorded since a point in time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageSummary {
+    pub since: Option<DateTime<Utc>>,
+    pub sessions: i64,
+    pub runs: i64,
+    pub usage: TokenUsage,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum AgentStatus {
+    Queued,
     Running,
     Paused,
     Completed,
@@ -124,6 +227,7 @@ pub enum AgentStatus {
 impl AgentStatus {
     pub fn as_str(&self) -> &'static str {
         match self {
+            Self::Queued => "queued",
             Self::Running => "running",
             Self::Paused => "paused",
             Self::Completed => "completed",
@@ -131,8 +235,10 @@ impl AgentStatus {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
+            "queued" => Some(Self::Queued),
             "running" => Some(Self::Running),
             "paused" => Some(Self::Paused),
             "completed" => Some(Self::Completed),
@@ -142,15 +248,179 @@ impl AgentStatus {
     }
 }
 
+/// How Claude handles tool permission prompts (`--permission-mode`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionMode {
+    Default,
+    AcceptEdits,
+    Plan,
+    BypassPermissions,
+}
+
+impl PermissionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::AcceptEdits => "acceptEdits",
+            Self::Plan => "plan",
+            Self::BypassPermissions => "bypassPermissions",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "default" => Some(Self::Default),
+            "acceptEdits" => Some(Self::AcceptEdits),
+            "plan" => Some(Self::Plan),
+            "bypassPermissions" => Some(Self::BypassPermissions),
+            _ => None,
+        }
+    }
+}
+
+/// How a session's run timeout is measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutMode {
+    /// From the start of the run.
+    #[default]
+    Total,
+    /// From the last event Claude streamed, so long but active runs survive.
+    Idle,
+}
+
+impl TimeoutMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Total => "total",
+            Self::Idle => "idle",
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "total" => Some(Self::Total),
+            "idle" => Some(Self::Idle),
+            _ => None,
+        }
+    }
+}
+
+/// One entry in a session transcript. Plain chat turns are `text`; the
+/// Claude stream also produces `thinking`, `tool_use` (with `tool_name` and
+/// `tool_input`), `tool_result` (linked by `tool_use_id`) and `init` rows.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentMessage {
     pub id: String,
     pub session_id: String,
     pub role: String,
     pub content: String,
+    #[serde(default = "default_content_type")]
+    pub content_type: String,
+    #[serde(default)]
+    pub tool_name: Option<String>,
+    #[serde(default)]
+    pub tool_input: Option<serde_json::Value>,
+    #[serde(default)]
+    pub tool_use_id: Option<String>,
+    #[serde(default)]
+    pub is_error: bool,
     pub timestamp: DateTime<Utc>,
 }
 
+fn default_content_type() -> String {
+    "text".to_string()
+}
+
+impl AgentMessage {
+    pub fn new(session_id: &str, role: &str, content_type: &str, content: &str) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            content_type: content_type.to_string(),
+            tool_name: None,
+            tool_input: None,
+            tool_use_id: None,
+            is_error: false,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// A file in a session's working directory or one of its snapshots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionFile {
+    /// Relative to the directory, `/`-separated.
+    pub path: String,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+}
+
+// ── Permissions ──
+
+/// A tool call Claude wants to make that needs the user's approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionRequest {
+    pub id: String,
+    pub session_id: String,
+    pub tool_name: String,
+    pub input: serde_json::Value,
+    pub tool_use_id: Option<String>,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// The answer to a [`PermissionRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionDecision {
+    pub allow: bool,
+    /// Shown to Claude when denied.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// A user's response to a pending [`PermissionRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvePermission {
+    pub allow: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// When allowing, also allow this tool from now on for the session or
+    /// the session's profile.
+    #[serde(default)]
+    pub remember: Option<PermissionScope>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionScope {
+    Session,
+    Profile,
+}
+
+/// Allows a tool without asking, for one session or every session started
+/// with a profile. Exactly one of `session_id` and `profile` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionRule {
+    pub id: String,
+    pub session_id: Option<String>,
+    pub profile: Option<String>,
+    /// Exact tool name, e.g. `Bash` or `mcp__fetch__fetch`.
+    pub tool_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatePermissionRule {
+    pub session_id: Option<String>,
+    pub profile: Option<String>,
+    pub tool_name: String,
+}
+
 // ── Notifications ──
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -163,6 +433,60 @@ pub struct Notification {
     pub created_at: DateTime<Utc>,
 }
 
+/// Filters for listing notifications. `None` fields are not filtered on.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationFilter {
+    pub read: Option<bool>,
+    pub integration_id: Option<String>,
+    pub notification_type: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+// ── Schedules ──
+
+/// A prompt run as a new agent session on a cron schedule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub name: String,
+    pub cron: String,
+    pub prompt: String,
+    pub working_directory: Option<String>,
+    pub dangerously_skip_permissions: bool,
+    pub enabled: bool,
+    /// Defined in `[[agents.schedules]]`; read-only through the API.
+    pub from_config: bool,
+    /// `None` while disabled.
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_session_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSchedule {
+    pub name: String,
+    pub cron: String,
+    pub prompt: String,
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub dangerously_skip_permissions: bool,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateSchedule {
+    pub name: Option<String>,
+    pub cron: Option<String>,
+    pub prompt: Option<String>,
+    pub directory: Option<String>,
+    pub dangerously_skip_permissions: Option<bool>,
+    pub enabled: Option<bool>,
+}
+
 // ── Integrations ──
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -191,6 +515,10 @@ pub struct ServerStatus {
     pub mcp_servers: Vec<String>,
     pub active_agent_sessions: usize,
     pub pending_tasks: usize,
+    #[serde(default)]
+    pub usage_today: TokenUsage,
+    #[serde(default)]
+    pub usage_total: TokenUsage,
 }
 
 // ── WebSocket Events ──
@@ -208,6 +536,14 @@ pub enum WsEvent {
     },
     AgentStatusChanged { session_id: String, status: AgentStatus },
     Notification(Notification),
+    NotificationRead { ids: Vec<String> },
+    NotificationDeleted { id: String },
+    PermissionRequested(PermissionRequest),
+    PermissionResolved {
+        session_id: String,
+        request_id: String,
+        allow: bool,
+    },
 }
 
 impl Task {
@@ -227,3 +563,117 @@ impl Task {
         }
     }
 }
+
+impl AgentSession {
+    /// A new running session with no subprocess state yet.
+    pub fn new(prompt: &str, model: &str) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            prompt: prompt.to_string(),
+            status: AgentStatus::Running,
+            model: model.to_string(),
+            claude_session_id: None,
+            working_directory: None,
+            dangerously_skip_permissions: false,
+            usage: TokenUsage::default(),
+            queue_position: None,
+            schedule_id: None,
+            task_id: None,
+            complete_task: false,
+            append_system_prompt: None,
+            allowed_tools: Vec::new(),
+            disallowed_tools: Vec::new(),
+            max_turns: None,
+            mcp_servers: None,
+            profile: None,
+            permission_mode: None,
+            timeout_secs: None,
+            startup_timeout_secs: None,
+            timeout_mode: None,
+            parent_session_id: None,
+            worktree_repo: None,
+            worktree_branch: None,
+            starred: false,
+            backend: None,
+            started_at: Utc::now(),
+            completed_at: None,
+        }
+    }
+}
+
+impl Schedule {
+    pub fn new(input: CreateSchedule) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: input.name,
+            cron: input.cron,
+            prompt: input.prompt,
+            working_directory: input.directory,
+            dangerously_skip_permissions: input.dangerously_skip_permissions,
+            enabled: input.enabled.unwrap_or(true),
+            from_config: false,
+            next_run_at: None,
+            last_run_at: None,
+            last_session_id: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+impl TaskComment {
+    pub fn new(task_id: &str, author: &str, content: &str, session_id: Option<&str>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            task_id: task_id.to_string(),
+            author: author.to_string(),
+            content: content.to_string(),
+            session_id: session_id.map(String::from),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+impl PermissionRequest {
+    pub fn new(
+        session_id: &str,
+        tool_name: &str,
+        input: serde_json::Value,
+        tool_use_id: Option<&str>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            tool_name: tool_name.to_string(),
+            input,
+            tool_use_id: tool_use_id.map(String::from),
+            requested_at: Utc::now(),
+        }
+    }
+}
+
+impl PermissionRule {
+    pub fn new(input: CreatePermissionRule) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            session_id: input.session_id,
+            profile: input.profile,
+            tool_name: input.tool_name,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+impl Notification {
+    pub fn new(notification_type: &str, message: &str, integration_id: Option<&str>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            notification_type: notification_type.to_string(),
+            message: message.to_string(),
+            read: false,
+            integration_id: integration_id.map(String::from),
+            created_at: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! Cron expressions for scheduled agent sessions.
+//!
+//! Standard five-field syntax (`minute hour day-of-month month day-of-week`),
+//! evaluated in the server's local time zone. Each field accepts `*`, single
+//! values, ranges (`1-5`), steps (`*/15`, `0-30/10`) and comma-separated
+//! lists. Months and weekdays also accept three-letter names (`jan`, `mon`),
+//! and both 0 and 7 mean Sunday. As in cron, when both day fields are
+//! restricted a time matches if either one does.
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, LocalResult, NaiveDate, NaiveDateTime, Timelike, Utc,
+};
+use std::fmt;
+use std::str::FromStr;
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead to search for the next run before giving up (e.g. `0 0 30 2 *`).
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+
+/// A parsed cron expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    /// Bit 0 is Sunday.
+    days_of_week: u64,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronError(String);
+
+impl fmt::Display for CronError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CronError {}
+
+impl FromStr for Cron {
+    type Err = CronError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(CronError(format!(
+                "expected 5 fields (minute hour day month weekday), got {}",
+                fields.len()
+            )));
+        };
+
+        let mut days_of_week = parse_field(dow, "weekday", 0, 7, DAY_NAMES)?;
+        // 7 is an alias for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            source: fields.join(" "),
+            minutes: parse_field(minute, "minute", 0, 59, &[])?,
+            hours: parse_field(hour, "hour", 0, 23, &[])?,
+            days_of_month: parse_field(dom, "day", 1, 31, &[])?,
+            months: parse_field(month, "month", 1, 12, MONTH_NAMES)?,
+            days_of_week,
+            dom_restricted: !dom.starts_with('*'),
+            dow_restricted: !dow.starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Cron {
+    /// The first matching time strictly after `after`, or `None` if the
+    /// expression never matches (e.g. February 30th).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local = after.with_timezone(&Local).naive_local();
+        let mut t = local.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = local + Duration::days(MAX_SEARCH_DAYS);
+
+        while t <= limit {
+            if !bit(self.months, t.month()) {
+                t = start_of_next_month(t.date())?;
+                continue;
+            }
+            if !self.day_matches(t.date()) {
+                t = start_of_day(t.date().succ_opt()?);
+                continue;
+            }
+            if !bit(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+                continue;
+            }
+            if !bit(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+                continue;
+            }
+
+            // Times skipped by a DST change don't exist; try the next match.
+            // Times repeated by one run the first time round (`Local` may
+            // list that one second, so `earliest()` won't do)
+            match t.and_local_timezone(Local) {
+                LocalResult::Single(found) => return Some(found.with_timezone(&Utc)),
+                LocalResult::Ambiguous(a, b) => return Some(a.min(b).with_timezone(&Utc)),
+                LocalResult::None => t += Duration::minutes(1),
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let dom = bit(self.days_of_month, date.day());
+        let dow = bit(self.days_of_week, date.weekday().num_days_from_sunday());
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+}
+
+fn bit(set: u64, n: u32) -> bool {
+    set & (1 << n) != 0
+}
+
+fn start_of_day(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default()
+}
+
+fn start_of_next_month(date: NaiveDate) -> Option<NaiveDateTime> {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1).map(start_of_day)
+}
+
+/// Parse one field into a bitset of allowed values.
+fn parse_field(field: &str, label: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, CronError> {
+    let mut set = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| CronError(format!("invalid {label} step '{step}'")))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (
+                parse_value(a, label, min, max, names)?,
+                parse_value(b, label, min, max, names)?,
+            )
+        } else {
+            let value = parse_value(range, label, min, max, names)?;
+            // "5/15" means every 15 starting at 5
+            (value, if step > 1 { max } else { value })
+        };
+
+        if start > end {
+            return Err(CronError(format!("invalid {label} range '{range}'")));
+        }
+        for n in (start..=end).step_by(step as usize) {
+            set |= 1 << n;
+        }
+    }
+
+    Ok(set)
+}
+
+fn parse_value(value: &str, label: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, CronError> {
+    let lower = value.to_ascii_lowercase();
+    if let Some(i) = names.iter().position(|n| *n == lower) {
+        // Names start at the field minimum: jan = 1, sun = 0
+        return Ok(i as u32 + min);
+    }
+
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|n| (min..=max).contains(n))
+        .ok_or_else(|| CronError(format!("invalid {label} '{value}' (expected {min}-{max})")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::sync::Once;
+
+    fn cron(expr: &str) -> Cron {
+        expr.parse().unwrap()
+    }
+
+    fn error(expr: &str) -> String {
+        expr.parse::<Cron>().unwrap_err().to_string()
+    }
+
+    fn set(values: &[u32]) -> u64 {
+        values.iter().fold(0, |set, n| set | 1 << n)
+    }
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    /// Evaluate `Local` as US Eastern time: EST (UTC-5), with EDT (UTC-4)
+    /// from the second Sunday in March to the first Sunday in November.
+    fn eastern_time() {
+        static TZ: Once = Once::new();
+        TZ.call_once(|| std::env::set_var("TZ", "EST5EDT,M3.2.0,M11.1.0"));
+    }
+
+    #[test]
+    fn parses_fields() {
+        let c = cron("*/15 9-17 * * mon-fri");
+        assert_eq!(c.minutes, set(&[0, 15, 30, 45]));
+        assert_eq!(c.hours, set(&[9, 10, 11, 12, 13, 14, 15, 16, 17]));
+        assert_eq!(c.days_of_month, set(&(1..=31).collect::<Vec<_>>()));
+        assert_eq!(c.months, set(&(1..=12).collect::<Vec<_>>()));
+        assert_eq!(c.days_of_week, set(&[1, 2, 3, 4, 5]));
+        assert!(!c.dom_restricted);
+        assert!(c.dow_restricted);
+    }
+
+    #[test]
+    fn parses_lists_steps_and_names() {
+        assert_eq!(cron("5/15 * * * *").minutes, set(&[5, 20, 35, 50]));
+        assert_eq!(cron("0-30/10 * * * *").minutes, set(&[0, 10, 20, 30]));
+        assert_eq!(cron("1,2,40-42 * * * *").minutes, set(&[1, 2, 40, 41, 42]));
+
+        let named = cron("0 0 * JAN,dec sun");
+        let numbered = cron("0 0 * 1,12 7");
+        assert_eq!(named.months, set(&[1, 12]));
+        assert_eq!(named.months, numbered.months);
+        assert_eq!(named.days_of_week, set(&[0]));
+        assert_eq!(named.days_of_week, numbered.days_of_week);
+        assert_eq!(cron("0 0 * * 5-7").days_of_week, set(&[0, 5, 6]));
+    }
+
+    #[test]
+    fn displays_normalised_source() {
+        assert_eq!(cron("  0  9 * *   mon ").to_string(), "0 9 * * mon");
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert_eq!(
+            error("* * * *"),
+            "expected 5 fields (minute hour day month weekday), got 4"
+        );
+        assert_eq!(
+            error("* * * * * *"),
+            "expected 5 fields (minute hour day month weekday), got 6"
+        );
+        assert_eq!(error("60 * * * *"), "invalid minute '60' (expected 0-59)");
+        assert_eq!(error("* 24 * * *"), "invalid hour '24' (expected 0-23)");
+        assert_eq!(error("* * 0 * *"), "invalid day '0' (expected 1-31)");
+        assert_eq!(error("* * * 13 *"), "invalid month '13' (expected 1-12)");
+        assert_eq!(error("* * * * 8"), "invalid weekday '8' (expected 0-7)");
+        assert_eq!(error("* * * foo *"), "invalid month 'foo' (expected 1-12)");
+        assert_eq!(error("*/0 * * * *"), "invalid minute step '0'");
+        assert_eq!(error("*/x * * * *"), "invalid minute step 'x'");
+        assert_eq!(error("30-10 * * * *"), "invalid minute range '30-10'");
+        assert_eq!(error("1,,2 * * * *"), "invalid minute '' (expected 0-59)");
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // 2026-11-13 is a Friday, 2026-11-06 a Friday and 2026-11-10 a Tuesday
+        let friday_13 = NaiveDate::from_ymd_opt(2026, 11, 13).unwrap();
+        let friday_6 = NaiveDate::from_ymd_opt(2026, 11, 6).unwrap();
+        let tuesday_10 = NaiveDate::from_ymd_opt(2026, 11, 10).unwrap();
+        let tuesday_13 = NaiveDate::from_ymd_opt(2026, 10, 13).unwrap();
+
+        let both = cron("0 0 13 * fri");
+        assert!(both.day_matches(friday_13));
+        assert!(both.day_matches(friday_6));
+        assert!(both.day_matches(tuesday_13));
+        assert!(!both.day_matches(tuesday_10));
+
+        let dom_only = cron("0 0 13 * *");
+        assert!(dom_only.day_matches(tuesday_13));
+        assert!(!dom_only.day_matches(friday_6));
+
+        let dow_only = cron("0 0 * * fri");
+        assert!(dow_only.day_matches(friday_6));
+        assert!(!dow_only.day_matches(tuesday_13));
+    }
+
+    #[test]
+    fn next_after_finds_following_match() {
+        eastern_time();
+        // 09:00 EST is 14:00 UTC
+        let c = cron("0 9 * * mon-fri");
+        // Friday 2026-01-09 09:00 has just passed: next is Monday
+        assert_eq!(c.next_after(utc(2026, 1, 9, 14, 0)), Some(utc(2026, 1, 12, 14, 0)));
+        assert_eq!(c.next_after(utc(2026, 1, 12, 13, 59)), Some(utc(2026, 1, 12, 14, 0)));
+        // Seconds are ignored, but the result is strictly later
+        assert_eq!(
+            cron("* * * * *").next_after(utc(2026, 1, 12, 14, 0) + Duration::seconds(30)),
+            Some(utc(2026, 1, 12, 14, 1))
+        );
+        // Over a year boundary
+        assert_eq!(
+            cron("0 0 1 jan *").next_after(utc(2026, 6, 1, 0, 0)),
+            Some(utc(2027, 1, 1, 5, 0))
+        );
+    }
+
+    #[test]
+    fn next_after_gives_up_on_impossible_dates() {
+        eastern_time();
+        assert_eq!(cron("0 0 30 feb *").next_after(utc(2026, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn next_after_skips_time_lost_to_dst() {
+        eastern_time();
+        // On 2026-03-08 clocks jump from 02:00 EST to 03:00 EDT, so 02:30
+        // doesn't happen that day
+        let c = cron("30 2 * * *");
+        assert_eq!(c.next_after(utc(2026, 3, 8, 5, 0)), Some(utc(2026, 3, 9, 6, 30)));
+
+        // An hourly job goes straight from 01:00 EST to 03:00 EDT
+        let hourly = cron("0 * * * *");
+        assert_eq!(hourly.next_after(utc(2026, 3, 8, 6, 0)), Some(utc(2026, 3, 8, 7, 0)));
+    }
+
+    #[test]
+    fn next_after_fires_once_in_repeated_dst_hour() {
+        eastern_time();
+        // On 2026-11-01 clocks go back from 02:00 EDT to 01:00 EST, so 01:30
+        // happens twice; only the first (EDT) one fires
+        let c = cron("30 1 * * *");
+        let first = c.next_after(utc(2026, 11, 1, 4, 0));
+        assert_eq!(first, Some(utc(2026, 11, 1, 5, 30)));
+        assert_eq!(c.next_after(first.unwrap()), Some(utc(2026, 11, 2, 6, 30)));
+    }
+}
@@ -25,7 +25,7 @@ pub async fn register_builtin_integrations(
                 let mut val = toml::Value::Table(table);
                 resolve_env_values(&mut val);
                 let values = match val {
-                    toml::Value::Table(t) => t.into_iter().map(|(k, v)| (k, v)).collect(),
+                    toml::Value::Table(t) => t.into_iter().collect(),
                     _ => HashMap::new(),
                 };
                 (values, tick)
@@ -1,50 +1,219 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use porter_core::db::Database;
 use porter_core::integrations::*;
-use porter_core::models::Notification;
+use porter_core::models::{CreateTask, Notification, Task, TaskStatus, UpdateTask};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
-pub struct TaskIntegration;
+/// Default reminder lead times (minutes before the due date).
+const DEFAULT_REMINDER_LEADS: &[i64] = &[60];
+
+const ID: &str = "tasks";
+
+pub struct TaskIntegration {
+    db: Option<Database>,
+    /// Minutes before a task's due date at which to send a reminder, ascending.
+    reminder_leads: Vec<i64>,
+}
+
+/// Reminders already sent for a task, stored in `integrations_state` under
+/// `reminders:{task_id}`. Reset whenever the task's due date changes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ReminderState {
+    due_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    sent_leads: Vec<i64>,
+    #[serde(default)]
+    overdue: bool,
+}
+
+/// Forget the reminders sent for `task_id`, once the task is deleted.
+pub async fn clear_reminders(db: &Database, task_id: &str) -> anyhow::Result<()> {
+    db.delete_integration_state(ID, &reminder_key(task_id)).await?;
+    Ok(())
+}
+
+fn reminder_key(task_id: &str) -> String {
+    format!("reminders:{task_id}")
+}
 
 impl TaskIntegration {
     pub fn new() -> Self {
-        Self
+        Self {
+            db: None,
+            reminder_leads: DEFAULT_REMINDER_LEADS.to_vec(),
+        }
+    }
+
+    fn db(&self) -> anyhow::Result<&Database> {
+        self.db
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Task integration not initialized"))
+    }
+}
+
+impl Default for TaskIntegration {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[async_trait]
 impl Integration for TaskIntegration {
     fn id(&self) -> &str {
-        "tasks"
+        ID
     }
 
     fn name(&self) -> &str {
         "Task Management"
     }
 
-    async fn init(&mut self, _config: &IntegrationConfig) -> anyhow::Result<()> {
-        tracing::info!("Task integration initialized");
+    async fn init(&mut self, config: &IntegrationConfig) -> anyhow::Result<()> {
+        self.db = Some(config.db.clone());
+
+        if let Some(value) = config.values.get("reminder_lead_minutes") {
+            let leads = value
+                .as_array()
+                .ok_or_else(|| anyhow::anyhow!("reminder_lead_minutes must be an array"))?;
+            let mut leads: Vec<i64> = leads
+                .iter()
+                .map(|v| {
+                    v.as_integer().filter(|m| *m > 0).ok_or_else(|| {
+                        anyhow::anyhow!("reminder_lead_minutes must contain positive integers")
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?;
+            leads.sort_unstable();
+            leads.dedup();
+            self.reminder_leads = leads;
+        }
+
+        tracing::info!(reminder_leads = ?self.reminder_leads, "Task integration initialized");
         Ok(())
     }
 
     async fn handle(&self, action: Action) -> anyhow::Result<ActionResult> {
+        let db = self.db()?;
+
         match action.name.as_str() {
-            "create" | "list" | "update" | "delete" => Ok(ActionResult {
-                success: true,
-                message: format!("Task action '{}' handled", action.name),
-                data: Some(action.params),
-            }),
-            _ => Ok(ActionResult {
-                success: false,
-                message: format!("Unknown task action: {}", action.name),
-                data: None,
-            }),
+            "create_task" => {
+                let input: CreateTask = match parse_params(&action.params) {
+                    Ok(input) => input,
+                    Err(result) => return Ok(result),
+                };
+                let task = db.create_task(input).await?;
+                Ok(success(format!("Created task '{}'", task.title), &task))
+            }
+            "list_tasks" => {
+                let status = action.params["status"].as_str();
+                let tasks = db.list_tasks(status).await?;
+                Ok(success(format!("Found {} tasks", tasks.len()), &tasks))
+            }
+            "update_task" => {
+                let Some(id) = action.params["id"].as_str() else {
+                    return Ok(failure("Missing task id"));
+                };
+                let input: UpdateTask = match parse_params(&action.params) {
+                    Ok(input) => input,
+                    Err(result) => return Ok(result),
+                };
+                match db.update_task(id, input).await? {
+                    Some(task) => Ok(success(format!("Updated task '{}'", task.title), &task)),
+                    None => Ok(failure(format!("Task not found: {id}"))),
+                }
+            }
+            "complete_task" => {
+                let Some(id) = action.params["id"].as_str() else {
+                    return Ok(failure("Missing task id"));
+                };
+                let input = UpdateTask {
+                    title: None,
+                    description: None,
+                    status: Some(TaskStatus::Completed),
+                    priority: None,
+                    tags: None,
+                    due_date: None,
+                };
+                match db.update_task(id, input).await? {
+                    Some(task) => Ok(success(format!("Completed task '{}'", task.title), &task)),
+                    None => Ok(failure(format!("Task not found: {id}"))),
+                }
+            }
+            "delete_task" => {
+                let Some(id) = action.params["id"].as_str() else {
+                    return Ok(failure("Missing task id"));
+                };
+                let Some(task) = db.get_task(id).await? else {
+                    return Ok(failure(format!("Task not found: {id}")));
+                };
+                db.delete_task(id).await?;
+                clear_reminders(db, id).await?;
+                Ok(success(format!("Deleted task '{}'", task.title), &task))
+            }
+            _ => Ok(failure(format!("Unknown task action: {}", action.name))),
         }
     }
 
+    /// Emit "due soon" and "overdue" reminders for open tasks. Each reminder
+    /// is sent once per due date; progress is kept in `integrations_state` so
+    /// restarts don't repeat them.
     async fn tick(&self) -> anyhow::Result<Vec<Notification>> {
-        Ok(vec![])
+        let db = self.db()?;
+        let now = Utc::now();
+        let max_lead = self.reminder_leads.last().copied().unwrap_or(0);
+        let tasks = db
+            .list_open_tasks_due_before(now + Duration::minutes(max_lead))
+            .await?;
+
+        let mut notifications = Vec::new();
+        for task in tasks {
+            let Some(due) = task.due_date else { continue };
+            let key = reminder_key(&task.id);
+
+            let mut state: ReminderState = db
+                .get_integration_state(self.id(), &key)
+                .await?
+                .and_then(|v| serde_json::from_str(&v).ok())
+                .unwrap_or_default();
+            if state.due_date != Some(due) {
+                state = ReminderState {
+                    due_date: Some(due),
+                    ..Default::default()
+                };
+            }
+
+            let Some(notification) = self.next_reminder(&task, due, now, &mut state) else {
+                continue;
+            };
+            // Stored before it's marked sent, so a failure means the
+            // reminder is retried next tick rather than lost
+            let notification = db
+                .create_notification(
+                    &notification.notification_type,
+                    &notification.message,
+                    notification.integration_id.as_deref(),
+                )
+                .await?;
+            db.set_integration_state(self.id(), &key, &serde_json::to_string(&state)?)
+                .await?;
+            notifications.push(notification);
+        }
+
+        Ok(notifications)
     }
 
     fn capabilities(&self) -> Vec<Capability> {
+        let status_enum = serde_json::json!(["pending", "in_progress", "completed", "cancelled"]);
+        let priority_enum = serde_json::json!(["low", "medium", "high", "urgent"]);
+        let id_only = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" }
+            },
+            "required": ["id"]
+        });
+
         vec![
             Capability {
                 name: "create_task".to_string(),
@@ -52,9 +221,11 @@ impl Integration for TaskIntegration {
                 parameters: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "title": { "type": "string" },
+                        "title": { "type": "string", "minLength": 1 },
                         "description": { "type": "string" },
-                        "priority": { "type": "string", "enum": ["low", "medium", "high", "urgent"] }
+                        "priority": { "type": "string", "enum": priority_enum },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "due_date": { "type": "string" }
                     },
                     "required": ["title"]
                 }),
@@ -65,10 +236,207 @@ impl Integration for TaskIntegration {
                 parameters: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "status": { "type": "string", "enum": ["pending", "in_progress", "completed", "cancelled"] }
+                        "status": { "type": "string", "enum": status_enum }
                     }
                 }),
             },
+            Capability {
+                name: "update_task".to_string(),
+                description: "Update fields of an existing task".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "title": { "type": "string", "minLength": 1 },
+                        "description": { "type": "string" },
+                        "status": { "type": "string", "enum": status_enum },
+                        "priority": { "type": "string", "enum": priority_enum },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "due_date": { "type": "string" }
+                    },
+                    "required": ["id"]
+                }),
+            },
+            Capability {
+                name: "delete_task".to_string(),
+                description: "Delete a task".to_string(),
+                parameters: id_only.clone(),
+            },
+            Capability {
+                name: "complete_task".to_string(),
+                description: "Mark a task as completed".to_string(),
+                parameters: id_only,
+            },
         ]
     }
 }
+
+impl TaskIntegration {
+    /// Work out which reminder (if any) is due for `task` and record it in
+    /// `state`. Only the most urgent unsent reminder is returned, so a task
+    /// that crosses several lead times between ticks notifies once.
+    fn next_reminder(
+        &self,
+        task: &Task,
+        due: DateTime<Utc>,
+        now: DateTime<Utc>,
+        state: &mut ReminderState,
+    ) -> Option<Notification> {
+        if now >= due {
+            if state.overdue {
+                return None;
+            }
+            state.overdue = true;
+            state.sent_leads = self.reminder_leads.clone();
+            return Some(Notification::new(
+                "task_overdue",
+                &format!("Task '{}' is overdue", task.title),
+                Some(self.id()),
+            ));
+        }
+
+        let remaining = due - now;
+        let lead = *self
+            .reminder_leads
+            .iter()
+            .find(|&&lead| remaining <= Duration::minutes(lead))?;
+        if state.sent_leads.contains(&lead) {
+            return None;
+        }
+
+        // Mark this lead and every longer one as sent
+        state.sent_leads = self
+            .reminder_leads
+            .iter()
+            .copied()
+            .filter(|&l| l >= lead)
+            .collect();
+        Some(Notification::new(
+            "task_due_soon",
+            &format!("Task '{}' is due within {}", task.title, format_lead(lead)),
+            Some(self.id()),
+        ))
+    }
+}
+
+/// Render a lead time in minutes as e.g. "30m", "1h", "2d".
+fn format_lead(minutes: i64) -> String {
+    if minutes % (24 * 60) == 0 {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+/// Deserialize action params, turning a parse error into a failed `ActionResult`.
+fn parse_params<T: DeserializeOwned>(params: &serde_json::Value) -> Result<T, ActionResult> {
+    serde_json::from_value(params.clone())
+        .map_err(|e| failure(format!("Invalid parameters: {e}")))
+}
+
+fn success(message: String, data: &impl serde::Serialize) -> ActionResult {
+    ActionResult {
+        success: true,
+        message,
+        data: serde_json::to_value(data).ok(),
+    }
+}
+
+fn failure(message: impl Into<String>) -> ActionResult {
+    ActionResult {
+        success: false,
+        message: message.into(),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks(leads: &[i64]) -> TaskIntegration {
+        TaskIntegration {
+            db: None,
+            reminder_leads: leads.to_vec(),
+        }
+    }
+
+    fn task() -> Task {
+        Task::new(CreateTask {
+            title: "Ship it".to_string(),
+            description: None,
+            priority: None,
+            tags: None,
+            due_date: None,
+        })
+    }
+
+    /// The reminder `tasks` sends `before_due` ahead of a task's due date.
+    fn remind(
+        tasks: &TaskIntegration,
+        before_due: Duration,
+        state: &mut ReminderState,
+    ) -> Option<String> {
+        let due = Utc::now();
+        tasks
+            .next_reminder(&task(), due, due - before_due, state)
+            .map(|n| format!("{}: {}", n.notification_type, n.message))
+    }
+
+    #[test]
+    fn formats_lead_times() {
+        assert_eq!(format_lead(30), "30m");
+        assert_eq!(format_lead(90), "90m");
+        assert_eq!(format_lead(60), "1h");
+        assert_eq!(format_lead(180), "3h");
+        assert_eq!(format_lead(24 * 60), "1d");
+        assert_eq!(format_lead(2 * 24 * 60), "2d");
+    }
+
+    #[test]
+    fn reminds_within_lead_time() {
+        let tasks = tasks(&[60, 24 * 60]);
+        let mut state = ReminderState::default();
+
+        assert_eq!(remind(&tasks, Duration::hours(30), &mut state), None);
+        assert_eq!(
+            remind(&tasks, Duration::hours(10), &mut state).as_deref(),
+            Some("task_due_soon: Task 'Ship it' is due within 1d")
+        );
+        assert_eq!(state.sent_leads, vec![24 * 60]);
+        assert_eq!(remind(&tasks, Duration::hours(5), &mut state), None);
+        assert_eq!(
+            remind(&tasks, Duration::minutes(45), &mut state).as_deref(),
+            Some("task_due_soon: Task 'Ship it' is due within 1h")
+        );
+        assert_eq!(remind(&tasks, Duration::minutes(10), &mut state), None);
+    }
+
+    #[test]
+    fn sends_only_the_most_urgent_reminder() {
+        let tasks = tasks(&[60, 24 * 60]);
+        let mut state = ReminderState::default();
+
+        assert_eq!(
+            remind(&tasks, Duration::minutes(30), &mut state).as_deref(),
+            Some("task_due_soon: Task 'Ship it' is due within 1h")
+        );
+        assert_eq!(state.sent_leads, vec![60, 24 * 60]);
+        assert_eq!(remind(&tasks, Duration::minutes(20), &mut state), None);
+    }
+
+    #[test]
+    fn reports_overdue_once() {
+        let tasks = tasks(&[60]);
+        let mut state = ReminderState::default();
+
+        assert_eq!(
+            remind(&tasks, Duration::minutes(-5), &mut state).as_deref(),
+            Some("task_overdue: Task 'Ship it' is overdue")
+        );
+        assert!(state.overdue);
+        assert_eq!(remind(&tasks, Duration::minutes(-30), &mut state), None);
+    }
+}
@@ -1,21 +1,43 @@
 use crate::AppState;
+use axum::body::Body;
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::{Json, Router};
-use porter_core::agents::SessionOptions;
-use porter_core::models::{AgentMessage, AgentSession};
+use chrono::{DateTime, Utc};
+use porter_core::agents::{
+    BudgetExceeded, InvalidSessionOptions, MergeConflict, PolicyViolation, SessionBusy,
+    SessionOptions,
+};
+use porter_core::models::{
+    AgentMessage, AgentSession, PermissionMode, SessionFile, TimeoutMode, UsageSummary,
+};
 use serde::Deserialize;
+use tokio_util::io::ReaderStream;
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/api/agents", get(list_sessions).post(start_session))
+        .route("/api/agents/usage", get(usage_summary))
+        .route("/api/agents/queue", get(list_queue))
+        .route("/api/agents/profiles", get(list_profiles))
+        .route("/api/agents/prune", axum::routing::post(prune_sessions))
         .route("/api/agents/{id}", get(get_session).delete(delete_session))
         .route(
             "/api/agents/{id}/messages",
             get(get_messages).post(send_message),
         )
         .route("/api/agents/{id}/cancel", axum::routing::post(cancel_session))
+        .route("/api/agents/{id}/fork", axum::routing::post(fork_session))
+        .route("/api/agents/{id}/diff", get(worktree_diff))
+        .route("/api/agents/{id}/merge", axum::routing::post(merge_worktree))
+        .route("/api/agents/{id}/discard", axum::routing::post(discard_worktree))
+        .route("/api/agents/{id}/files", get(list_files))
+        .route("/api/agents/{id}/files/{*path}", get(download_file))
+        .route("/api/agents/{id}/snapshots", get(list_snapshots))
+        .route("/api/agents/{id}/queue", axum::routing::put(move_in_queue))
+        .route("/api/agents/{id}/star", axum::routing::put(star_session))
 }
 
 #[derive(Deserialize)]
@@ -23,12 +45,55 @@ struct SessionQuery {
     status: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct UsageQuery {
+    since: Option<DateTime<Utc>>,
+}
+
 #[derive(Deserialize)]
 struct StartSessionRequest {
     prompt: String,
     directory: Option<String>,
     #[serde(default)]
     dangerously_skip_permissions: bool,
+    model: Option<String>,
+    append_system_prompt: Option<String>,
+    #[serde(default)]
+    allowed_tools: Vec<String>,
+    #[serde(default)]
+    disallowed_tools: Vec<String>,
+    max_turns: Option<u32>,
+    /// Subset of the configured MCP servers; omit for all of them.
+    mcp_servers: Option<Vec<String>>,
+    /// `[agents.profiles]` entry supplying defaults for omitted fields.
+    profile: Option<String>,
+    permission_mode: Option<PermissionMode>,
+    timeout_secs: Option<u64>,
+    startup_timeout_secs: Option<u64>,
+    timeout_mode: Option<TimeoutMode>,
+    /// Run in a new git worktree and branch of `directory`'s repository.
+    #[serde(default)]
+    worktree: bool,
+    /// `[agents.backends]` entry to run on instead of Claude.
+    backend: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PruneRequest {
+    /// List what would be deleted without deleting it.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+struct StarRequest {
+    starred: bool,
+}
+
+#[derive(Deserialize)]
+struct FilesQuery {
+    /// Read from this snapshot instead of the working directory.
+    snapshot: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -36,6 +101,12 @@ struct SendMessageRequest {
     content: String,
 }
 
+#[derive(Deserialize)]
+struct MoveInQueueRequest {
+    /// 1-based; values past the end move the session to the back.
+    position: usize,
+}
+
 async fn list_sessions(
     State(state): State<AppState>,
     Query(query): Query<SessionQuery>,
@@ -58,6 +129,20 @@ async fn start_session(
     let opts = SessionOptions {
         working_directory: input.directory,
         dangerously_skip_permissions: input.dangerously_skip_permissions,
+        model: input.model,
+        append_system_prompt: input.append_system_prompt,
+        allowed_tools: input.allowed_tools,
+        disallowed_tools: input.disallowed_tools,
+        max_turns: input.max_turns,
+        mcp_servers: input.mcp_servers,
+        profile: input.profile,
+        permission_mode: input.permission_mode,
+        timeout_secs: input.timeout_secs,
+        startup_timeout_secs: input.startup_timeout_secs,
+        timeout_mode: input.timeout_mode,
+        worktree: input.worktree,
+        backend: input.backend,
+        ..Default::default()
     };
 
     let session = state
@@ -66,11 +151,64 @@ async fn start_session(
         .await
         .map_err(|e| {
             tracing::error!(error = %e, "Failed to start agent session");
-            StatusCode::INTERNAL_SERVER_ERROR
+            agent_error_status(&e)
         })?;
     Ok((StatusCode::CREATED, Json(session)))
 }
 
+async fn usage_summary(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageSummary>, StatusCode> {
+    let summary = state
+        .db
+        .agent_usage_summary(query.since)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to summarise agent usage");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(summary))
+}
+
+async fn list_queue(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<AgentSession>>, StatusCode> {
+    let queue = state.agent_manager.list_queue().await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to list agent queue");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(queue))
+}
+
+async fn list_profiles(State(state): State<AppState>) -> Json<Vec<String>> {
+    Json(state.agent_manager.profile_names())
+}
+
+async fn move_in_queue(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<MoveInQueueRequest>,
+) -> Result<Json<Vec<AgentSession>>, StatusCode> {
+    if input.position == 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let moved = state
+        .agent_manager
+        .move_in_queue(&id, input.position)
+        .await
+        .map_err(|e| {
+            tracing::error!(session_id = %id, error = %e, "Failed to reorder agent queue");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if !moved {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    list_queue(State(state)).await
+}
+
 async fn get_session(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -118,7 +256,10 @@ async fn send_message(
         .agent_manager
         .send_message(&id, &input.content)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            tracing::error!(session_id = %id, error = %e, "Failed to send agent message");
+            agent_error_status(&e)
+        })?;
 
     // Fetch the just-stored user message to return it
     let messages = state
@@ -153,6 +294,162 @@ async fn cancel_session(
     }
 }
 
+/// Apply `[agents.retention]` now. Returns the sessions deleted (or that
+/// would be, with `dry_run`).
+async fn prune_sessions(
+    State(state): State<AppState>,
+    Json(input): Json<PruneRequest>,
+) -> Result<Json<Vec<AgentSession>>, StatusCode> {
+    let pruned = state
+        .agent_manager
+        .prune_sessions(input.dry_run)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to prune agent sessions");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(pruned))
+}
+
+async fn star_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<StarRequest>,
+) -> Result<Json<AgentSession>, StatusCode> {
+    state
+        .agent_manager
+        .set_starred(&id, input.starred)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Branch a new session off this one's conversation so far.
+async fn fork_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<AgentSession>), StatusCode> {
+    state
+        .agent_manager
+        .fork_session(&id)
+        .await
+        .map_err(|e| {
+            tracing::error!(session_id = %id, error = %e, "Failed to fork agent session");
+            agent_error_status(&e)
+        })?
+        .map(|fork| (StatusCode::CREATED, Json(fork)))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// The session's worktree changes as a plain-text diff.
+async fn worktree_diff(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<String, StatusCode> {
+    state
+        .agent_manager
+        .worktree_diff(&id)
+        .await
+        .map_err(|e| {
+            tracing::error!(session_id = %id, error = %e, "Failed to diff worktree");
+            agent_error_status(&e)
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn merge_worktree(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let merged = state.agent_manager.merge_worktree(&id).await.map_err(|e| {
+        tracing::error!(session_id = %id, error = %e, "Failed to merge worktree");
+        agent_error_status(&e)
+    })?;
+
+    if merged {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+async fn discard_worktree(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let discarded = state.agent_manager.discard_worktree(&id).await.map_err(|e| {
+        tracing::error!(session_id = %id, error = %e, "Failed to discard worktree");
+        agent_error_status(&e)
+    })?;
+
+    if discarded {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+async fn list_files(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<FilesQuery>,
+) -> Result<Json<Vec<SessionFile>>, StatusCode> {
+    state
+        .agent_manager
+        .list_files(&id, query.snapshot.as_deref())
+        .await
+        .map_err(|e| {
+            tracing::error!(session_id = %id, error = %e, "Failed to list session files");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn download_file(
+    State(state): State<AppState>,
+    Path((id, path)): Path<(String, String)>,
+    Query(query): Query<FilesQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let file = state
+        .agent_manager
+        .file_path(&id, &path, query.snapshot.as_deref())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let content = tokio::fs::File::open(&file)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let len = content
+        .metadata()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .len();
+    let content_type = mime_guess::from_path(&file).first_or_octet_stream();
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_LENGTH, len.to_string()),
+        ],
+        Body::from_stream(ReaderStream::new(content)),
+    ))
+}
+
+async fn list_snapshots(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<String>>, StatusCode> {
+    state
+        .agent_manager
+        .list_snapshots(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
 async fn delete_session(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -161,7 +458,10 @@ async fn delete_session(
         .agent_manager
         .delete_session(&id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            tracing::error!(session_id = %id, error = %e, "Failed to delete agent session");
+            agent_error_status(&e)
+        })?;
 
     if deleted {
         Ok(StatusCode::NO_CONTENT)
@@ -169,3 +469,20 @@ async fn delete_session(
         Err(StatusCode::NOT_FOUND)
     }
 }
+
+/// Map an `AgentManager` error to a response status.
+pub(super) fn agent_error_status(e: &anyhow::Error) -> StatusCode {
+    if e.downcast_ref::<BudgetExceeded>().is_some() {
+        StatusCode::TOO_MANY_REQUESTS
+    } else if e.downcast_ref::<InvalidSessionOptions>().is_some() {
+        StatusCode::BAD_REQUEST
+    } else if e.downcast_ref::<PolicyViolation>().is_some() {
+        StatusCode::FORBIDDEN
+    } else if e.downcast_ref::<SessionBusy>().is_some()
+        || e.downcast_ref::<MergeConflict>().is_some()
+    {
+        StatusCode::CONFLICT
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
@@ -2,6 +2,7 @@ use crate::AppState;
 use axum::extract::State;
 use axum::routing::get;
 use axum::{Json, Router};
+use chrono::{Local, NaiveTime, Utc};
 use porter_core::models::ServerStatus;
 
 pub fn router() -> Router<AppState> {
@@ -28,6 +29,25 @@ async fn status(State(state): State<AppState>) -> Json<ServerStatus> {
         .await
         .unwrap_or(0) as usize;
 
+    let midnight = Local::now()
+        .date_naive()
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .map(|d| d.with_timezone(&Utc));
+    let usage_today = state
+        .db
+        .agent_usage_summary(midnight)
+        .await
+        .map(|s| s.usage)
+        .unwrap_or_default();
+    let usage_total = state
+        .db
+        .agent_usage_summary(None)
+        .await
+        .map(|s| s.usage)
+        .unwrap_or_default();
+
     let uptime = state.started_at.elapsed().as_secs();
 
     Json(ServerStatus {
@@ -38,5 +58,7 @@ async fn status(State(state): State<AppState>) -> Json<ServerStatus> {
         mcp_servers: state.agent_manager.mcp_server_names(),
         active_agent_sessions: active_sessions,
         pending_tasks,
+        usage_today,
+        usage_total,
     })
 }
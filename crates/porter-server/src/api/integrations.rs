@@ -1,9 +1,11 @@
 use crate::AppState;
-use axum::extract::State;
-use axum::routing::get;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
 use axum::{Json, Router};
+use porter_core::integrations::{Action, ActionResult};
 use porter_core::models::{IntegrationInfo, McpServerInfo};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Serialize)]
 struct IntegrationsResponse {
@@ -11,8 +13,23 @@ struct IntegrationsResponse {
     mcp_servers: Vec<McpServerInfo>,
 }
 
+#[derive(Deserialize)]
+struct ActionRequest {
+    #[serde(default = "empty_params")]
+    params: serde_json::Value,
+}
+
+fn empty_params() -> serde_json::Value {
+    serde_json::json!({})
+}
+
 pub fn router() -> Router<AppState> {
-    Router::new().route("/api/integrations", get(list_integrations))
+    Router::new()
+        .route("/api/integrations", get(list_integrations))
+        .route(
+            "/api/integrations/{id}/actions/{name}",
+            post(handle_action),
+        )
 }
 
 async fn list_integrations(State(state): State<AppState>) -> Json<IntegrationsResponse> {
@@ -44,3 +61,55 @@ async fn list_integrations(State(state): State<AppState>) -> Json<IntegrationsRe
         mcp_servers,
     })
 }
+
+/// Invoke a capability on a built-in integration.
+///
+/// Returns 404 for an unknown integration or action, 400 when `params` do not
+/// match the capability's schema, and 422 when the integration reports the
+/// action as unsuccessful.
+async fn handle_action(
+    State(state): State<AppState>,
+    Path((id, name)): Path<(String, String)>,
+    Json(input): Json<ActionRequest>,
+) -> Result<(StatusCode, Json<ActionResult>), StatusCode> {
+    let integration = state
+        .integration_registry
+        .get(&id)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .clone();
+
+    let capability = integration
+        .capabilities()
+        .into_iter()
+        .find(|c| c.name == name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if let Err(errors) = capability.validate_params(&input.params) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(ActionResult {
+                success: false,
+                message: "Invalid parameters".to_string(),
+                data: Some(serde_json::json!({ "errors": errors })),
+            }),
+        ));
+    }
+
+    let result = integration
+        .handle(Action {
+            name,
+            params: input.params,
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!(integration = %id, error = %e, "Integration action failed");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let status = if result.success {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    Ok((status, Json(result)))
+}
@@ -1,6 +1,9 @@
 mod agents;
 mod health;
 mod integrations;
+mod notifications;
+mod permissions;
+mod schedules;
 mod tasks;
 mod webhooks;
 
@@ -13,5 +16,8 @@ pub fn router() -> Router<AppState> {
         .merge(tasks::router())
         .merge(agents::router())
         .merge(integrations::router())
+        .merge(notifications::router())
+        .merge(permissions::router())
+        .merge(schedules::router())
         .merge(webhooks::router())
 }
@@ -0,0 +1,114 @@
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use porter_core::models::{Notification, NotificationFilter, WsEvent};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound on `limit` so a single request can't pull the whole table.
+const MAX_PAGE_SIZE: i64 = 500;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/notifications", get(list_notifications))
+        .route("/api/notifications/read-all", post(mark_all_read))
+        .route("/api/notifications/{id}", delete(delete_notification))
+        .route("/api/notifications/{id}/read", post(mark_read))
+}
+
+#[derive(Deserialize)]
+struct NotificationQuery {
+    read: Option<bool>,
+    integration_id: Option<String>,
+    #[serde(rename = "type")]
+    notification_type: Option<String>,
+    since: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct MarkAllQuery {
+    integration_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MarkAllResponse {
+    updated: usize,
+}
+
+async fn list_notifications(
+    State(state): State<AppState>,
+    Query(query): Query<NotificationQuery>,
+) -> Result<Json<Vec<Notification>>, StatusCode> {
+    let filter = NotificationFilter {
+        read: query.read,
+        integration_id: query.integration_id,
+        notification_type: query.notification_type,
+        since: query.since,
+        limit: Some(query.limit.unwrap_or(50).clamp(1, MAX_PAGE_SIZE)),
+        offset: Some(query.offset.unwrap_or(0).max(0)),
+    };
+
+    let notifications = state
+        .db
+        .list_notifications(&filter)
+        .await
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to list notifications");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(notifications))
+}
+
+async fn mark_read(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Notification>, StatusCode> {
+    let notification = state
+        .db
+        .mark_notification_read(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let _ = state.ws_tx.send(WsEvent::NotificationRead { ids: vec![id] });
+    Ok(Json(notification))
+}
+
+async fn mark_all_read(
+    State(state): State<AppState>,
+    Query(query): Query<MarkAllQuery>,
+) -> Result<Json<MarkAllResponse>, StatusCode> {
+    let ids = state
+        .db
+        .mark_all_notifications_read(query.integration_id.as_deref())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let updated = ids.len();
+    if !ids.is_empty() {
+        let _ = state.ws_tx.send(WsEvent::NotificationRead { ids });
+    }
+    Ok(Json(MarkAllResponse { updated }))
+}
+
+async fn delete_notification(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let deleted = state
+        .db
+        .delete_notification(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if deleted {
+        let _ = state.ws_tx.send(WsEvent::NotificationDeleted { id });
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
@@ -0,0 +1,123 @@
+use super::agents::agent_error_status;
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use porter_core::models::{
+    CreatePermissionRule, PermissionDecision, PermissionRequest, PermissionRule, ResolvePermission,
+};
+use serde::Deserialize;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/agents/{id}/permissions",
+            get(list_pending).post(request_permission),
+        )
+        .route("/api/agents/{id}/permissions/{req}", post(resolve_permission))
+        .route("/api/permission-rules", get(list_rules).post(create_rule))
+        .route("/api/permission-rules/{id}", delete(delete_rule))
+}
+
+/// Sent by `porter mcp` on behalf of Claude's `--permission-prompt-tool`.
+#[derive(Deserialize)]
+struct PermissionPrompt {
+    tool_name: String,
+    #[serde(default)]
+    input: serde_json::Value,
+    tool_use_id: Option<String>,
+}
+
+async fn list_pending(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Json<Vec<PermissionRequest>> {
+    Json(state.agent_manager.pending_permissions(&id))
+}
+
+/// Held open until the request is answered, times out or Porter shuts down.
+async fn request_permission(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<PermissionPrompt>,
+) -> Result<Json<PermissionDecision>, StatusCode> {
+    state
+        .agent_manager
+        .get_session(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let decision = state
+        .agent_manager
+        .request_permission(&id, &input.tool_name, input.input, input.tool_use_id.as_deref())
+        .await
+        .map_err(|e| {
+            tracing::error!(session_id = %id, error = %e, "Failed to handle permission request");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(decision))
+}
+
+async fn resolve_permission(
+    State(state): State<AppState>,
+    Path((id, req)): Path<(String, String)>,
+    Json(input): Json<ResolvePermission>,
+) -> Result<StatusCode, StatusCode> {
+    let resolved = state
+        .agent_manager
+        .resolve_permission(&id, &req, input)
+        .await
+        .map_err(|e| {
+            tracing::error!(session_id = %id, request_id = %req, error = %e, "Failed to resolve permission request");
+            agent_error_status(&e)
+        })?;
+
+    if resolved {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+async fn list_rules(State(state): State<AppState>) -> Result<Json<Vec<PermissionRule>>, StatusCode> {
+    let rules = state.db.list_permission_rules().await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to list permission rules");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(rules))
+}
+
+async fn create_rule(
+    State(state): State<AppState>,
+    Json(input): Json<CreatePermissionRule>,
+) -> Result<(StatusCode, Json<PermissionRule>), StatusCode> {
+    if input.session_id.is_some() == input.profile.is_some() || input.tool_name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let rule = PermissionRule::new(input);
+    state.db.add_permission_rule(&rule).await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to create permission rule");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok((StatusCode::CREATED, Json(rule)))
+}
+
+async fn delete_rule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let deleted = state
+        .db
+        .delete_permission_rule(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{scheduler, AppState};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use porter_core::models::{AgentSession, CreateSchedule, Schedule, UpdateSchedule};
+use porter_core::schedules::CronError;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/schedules", get(list_schedules).post(create_schedule))
+        .route(
+            "/api/schedules/{id}",
+            get(get_schedule).put(update_schedule).delete(delete_schedule),
+        )
+        .route("/api/schedules/{id}/run", post(run_schedule))
+}
+
+async fn list_schedules(State(state): State<AppState>) -> Result<Json<Vec<Schedule>>, StatusCode> {
+    let schedules = state.db.list_schedules().await.map_err(|e| {
+        tracing::error!(error = %e, "Failed to list schedules");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(schedules))
+}
+
+async fn create_schedule(
+    State(state): State<AppState>,
+    Json(input): Json<CreateSchedule>,
+) -> Result<(StatusCode, Json<Schedule>), StatusCode> {
+    if state
+        .db
+        .get_schedule_by_name(&input.name)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .is_some()
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let schedule = state
+        .db
+        .create_schedule(input, false)
+        .await
+        .map_err(schedule_error_status)?;
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+async fn get_schedule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Schedule>, StatusCode> {
+    state
+        .db
+        .get_schedule(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn update_schedule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<UpdateSchedule>,
+) -> Result<Json<Schedule>, StatusCode> {
+    editable_schedule(&state, &id).await?;
+
+    state
+        .db
+        .update_schedule(&id, input)
+        .await
+        .map_err(schedule_error_status)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn delete_schedule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    editable_schedule(&state, &id).await?;
+
+    let deleted = state
+        .db
+        .delete_schedule(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Fire a schedule now. Its regular next run is recomputed from now.
+async fn run_schedule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<AgentSession>), StatusCode> {
+    let schedule = state
+        .db
+        .get_schedule(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let session_id = scheduler::fire(&state, &schedule).await.map_err(|e| {
+        tracing::error!(schedule = %schedule.name, error = %e, "Failed to run schedule");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    // fire() reports start failures as notifications
+    let session_id = session_id.ok_or(StatusCode::UNPROCESSABLE_ENTITY)?;
+
+    let session = state
+        .agent_manager
+        .get_session(&session_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// 404 if the schedule doesn't exist, 409 if it comes from the config file
+/// (edit the config instead).
+async fn editable_schedule(state: &AppState, id: &str) -> Result<Schedule, StatusCode> {
+    let schedule = state
+        .db
+        .get_schedule(id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if schedule.from_config {
+        return Err(StatusCode::CONFLICT);
+    }
+    Ok(schedule)
+}
+
+fn schedule_error_status(e: anyhow::Error) -> StatusCode {
+    if e.downcast_ref::<CronError>().is_some() {
+        StatusCode::BAD_REQUEST
+    } else {
+        tracing::error!(error = %e, "Failed to save schedule");
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
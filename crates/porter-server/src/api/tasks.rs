@@ -1,11 +1,16 @@
 use crate::AppState;
 use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
-use axum::routing::get;
+use axum::routing::{get, post};
 use axum::{Json, Router};
-use porter_core::models::{CreateTask, Task, UpdateTask};
+use porter_core::agents::SessionOptions;
+use porter_core::models::{
+    AgentSession, CreateTask, CreateTaskComment, Task, TaskComment, UpdateTask,
+};
 use serde::Deserialize;
 
+use super::agents::agent_error_status;
+
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/api/tasks", get(list_tasks).post(create_task))
@@ -13,6 +18,11 @@ pub fn router() -> Router<AppState> {
             "/api/tasks/{id}",
             get(get_task).put(update_task).delete(delete_task),
         )
+        .route(
+            "/api/tasks/{id}/comments",
+            get(list_comments).post(add_comment),
+        )
+        .route("/api/tasks/{id}/agent", post(start_agent))
 }
 
 #[derive(Deserialize)]
@@ -20,6 +30,34 @@ struct TaskQuery {
     status: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct StartAgentRequest {
+    /// Extra instructions appended after the task details.
+    instructions: Option<String>,
+    directory: Option<String>,
+    #[serde(default)]
+    dangerously_skip_permissions: bool,
+    /// Mark the task completed when the session succeeds; otherwise the
+    /// agent only leaves a comment.
+    #[serde(default = "default_true")]
+    complete_task: bool,
+}
+
+impl Default for StartAgentRequest {
+    fn default() -> Self {
+        Self {
+            instructions: None,
+            directory: None,
+            dangerously_skip_permissions: false,
+            complete_task: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
 async fn list_tasks(
     State(state): State<AppState>,
     Query(query): Query<TaskQuery>,
@@ -86,9 +124,75 @@ async fn delete_task(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     if deleted {
+        if let Err(e) = porter_integrations::tasks::clear_reminders(&state.db, &id).await {
+            tracing::warn!(task_id = %id, error = %e, "Failed to clear task reminders");
+        }
         state.broadcast_task_deleted(&id);
         Ok(StatusCode::NO_CONTENT)
     } else {
         Err(StatusCode::NOT_FOUND)
     }
 }
+
+async fn list_comments(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<TaskComment>>, StatusCode> {
+    find_task(&state, &id).await?;
+    let comments = state
+        .db
+        .list_task_comments(&id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(comments))
+}
+
+async fn add_comment(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<CreateTaskComment>,
+) -> Result<(StatusCode, Json<TaskComment>), StatusCode> {
+    find_task(&state, &id).await?;
+    let comment = TaskComment::new(&id, "user", &input.content, None);
+    state
+        .db
+        .add_task_comment(&comment)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+/// Start an agent session working on this task.
+async fn start_agent(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    input: Option<Json<StartAgentRequest>>,
+) -> Result<(StatusCode, Json<AgentSession>), StatusCode> {
+    let task = find_task(&state, &id).await?;
+    let Json(input) = input.unwrap_or_default();
+
+    let opts = SessionOptions {
+        working_directory: input.directory,
+        dangerously_skip_permissions: input.dangerously_skip_permissions,
+        complete_task: input.complete_task,
+        ..Default::default()
+    };
+    let session = state
+        .agent_manager
+        .start_task_session(&task, input.instructions.as_deref(), opts)
+        .await
+        .map_err(|e| {
+            tracing::error!(task_id = %id, error = %e, "Failed to start agent session for task");
+            agent_error_status(&e)
+        })?;
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+async fn find_task(state: &AppState, id: &str) -> Result<Task, StatusCode> {
+    state
+        .db
+        .get_task(id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
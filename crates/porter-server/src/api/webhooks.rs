@@ -40,8 +40,8 @@ async fn handle_webhook(
         })?;
 
     // Persist and broadcast each notification
-    for notification in &notifications {
-        if let Err(e) = state
+    for notification in notifications {
+        let notification = match state
             .db
             .create_notification(
                 &notification.notification_type,
@@ -50,9 +50,13 @@ async fn handle_webhook(
             )
             .await
         {
-            tracing::error!(error = %e, "Failed to persist webhook notification");
-        }
-        let _ = state.ws_tx.send(WsEvent::Notification(notification.clone()));
+            Ok(saved) => saved,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to persist webhook notification");
+                notification
+            }
+        };
+        let _ = state.ws_tx.send(WsEvent::Notification(notification));
     }
 
     Ok(StatusCode::OK)
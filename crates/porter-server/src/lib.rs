@@ -1,17 +1,20 @@
 mod api;
 mod middleware;
+mod retention;
+mod scheduler;
 mod ws;
 
 use porter_core::agents::{AgentEvent, AgentManager};
-use porter_core::config::PorterConfig;
+use porter_core::config::{McpServerConfig, PorterConfig};
 use porter_core::db::{self, Database};
 use porter_core::integrations::IntegrationRegistry;
 use porter_core::models::{Task, WsEvent};
 use porter_integrations::register_builtin_integrations;
-use sqlx::sqlite::SqlitePoolOptions;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -21,6 +24,8 @@ pub struct AppState {
     pub agent_manager: Arc<AgentManager>,
     pub ws_tx: broadcast::Sender<WsEvent>,
     pub started_at: Instant,
+    /// Cancelled when the server begins shutting down.
+    pub shutdown: CancellationToken,
 }
 
 impl AppState {
@@ -39,16 +44,40 @@ impl AppState {
     }
 }
 
+/// Build the MCP server entry that points agent sessions back at this
+/// instance via `porter mcp`. Paths are made absolute because Claude runs
+/// MCP servers from the session's working directory.
+fn porter_mcp_server(config: &PorterConfig) -> Option<McpServerConfig> {
+    let config_path = config.source_path.as_ref()?;
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            tracing::warn!(error = %e, "Cannot locate porter binary, skipping porter MCP server");
+            return None;
+        }
+    };
+    let db_path = std::path::absolute(&config.instance.db_path).ok()?;
+
+    Some(McpServerConfig {
+        command: exe.to_string_lossy().into_owned(),
+        args: vec![
+            "mcp".to_string(),
+            "--config".to_string(),
+            config_path.to_string_lossy().into_owned(),
+            "--db".to_string(),
+            db_path.to_string_lossy().into_owned(),
+        ],
+        // Lets it call back for permission decisions
+        env: HashMap::from([(
+            "PORTER_URL".to_string(),
+            format!("http://127.0.0.1:{}", config.instance.port),
+        )]),
+    })
+}
+
 pub async fn run_server(config: PorterConfig) -> anyhow::Result<()> {
     // Database setup
-    let db_url = format!("sqlite:{}?mode=rwc", config.instance.db_path);
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
-        .await?;
-
-    db::run_migrations(&pool).await?;
-    let database = Database::new(pool);
+    let database = db::connect(&config.instance.db_path).await?;
 
     // Integration registry
     let mut registry = IntegrationRegistry::new();
@@ -57,10 +86,8 @@ pub async fn run_server(config: PorterConfig) -> anyhow::Result<()> {
     // Agent manager (with MCP server configs)
     let agent_manager = AgentManager::new(
         database.clone(),
-        config.agents.claude_binary.clone(),
-        config.agents.max_concurrent_sessions,
-        config.agents.default_model.clone(),
-        config.agents.mcp.clone(),
+        &config.agents,
+        porter_mcp_server(&config),
     );
 
     // WebSocket broadcast channel
@@ -76,33 +103,50 @@ pub async fn run_server(config: PorterConfig) -> anyhow::Result<()> {
         agent_manager: Arc::new(agent_manager),
         ws_tx: ws_tx.clone(),
         started_at: Instant::now(),
+        shutdown: CancellationToken::new(),
     };
 
     // Spawn background tick tasks for integrations that have a configured interval
+    let mut tick_tasks = Vec::new();
     for (integration, interval_secs) in tick_integrations {
         let db = database.clone();
         let tx = ws_tx.clone();
         let id = integration.id().to_string();
+        let shutdown = state.shutdown.clone();
         tracing::info!(integration = %id, interval_secs, "Spawning tick task");
 
-        tokio::spawn(async move {
+        tick_tasks.push(tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
             loop {
-                interval.tick().await;
+                // Only stop between ticks so a tick never half-applies
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
                 tracing::debug!(integration = %id, "Running tick");
                 match integration.tick().await {
                     Ok(notifications) => {
                         for notification in notifications {
-                            if let Err(e) = db
-                                .create_notification(
-                                    &notification.notification_type,
-                                    &notification.message,
-                                    notification.integration_id.as_deref(),
-                                )
-                                .await
-                            {
-                                tracing::error!(integration = %id, error = %e, "Failed to persist tick notification");
-                            }
+                            // Broadcast the stored copy so clients see the DB id;
+                            // the integration may have stored it already
+                            let stored = match db.get_notification(&notification.id).await {
+                                Ok(Some(saved)) => Ok(saved),
+                                _ => {
+                                    db.create_notification(
+                                        &notification.notification_type,
+                                        &notification.message,
+                                        notification.integration_id.as_deref(),
+                                    )
+                                    .await
+                                }
+                            };
+                            let notification = match stored {
+                                Ok(saved) => saved,
+                                Err(e) => {
+                                    tracing::error!(integration = %id, error = %e, "Failed to persist tick notification");
+                                    notification
+                                }
+                            };
                             let _ = tx.send(WsEvent::Notification(notification));
                         }
                     }
@@ -111,7 +155,8 @@ pub async fn run_server(config: PorterConfig) -> anyhow::Result<()> {
                     }
                 }
             }
-        });
+            tracing::debug!(integration = %id, "Tick task stopped");
+        }));
     }
 
     // Forward agent events to the WebSocket broadcast channel
@@ -133,12 +178,45 @@ pub async fn run_server(config: PorterConfig) -> anyhow::Result<()> {
                     AgentEvent::StatusChanged { session_id, status } => {
                         WsEvent::AgentStatusChanged { session_id, status }
                     }
+                    AgentEvent::Notification(notification) => WsEvent::Notification(notification),
+                    AgentEvent::TaskUpdated(task) => WsEvent::TaskUpdated(task),
+                    AgentEvent::PermissionRequested(request) => {
+                        WsEvent::PermissionRequested(request)
+                    }
+                    AgentEvent::PermissionResolved {
+                        session_id,
+                        request_id,
+                        allow,
+                    } => WsEvent::PermissionResolved {
+                        session_id,
+                        request_id,
+                        allow,
+                    },
                 };
                 let _ = ws_tx.send(ws_event);
             }
         });
     }
 
+    // Sessions left "running" by a previous process have no subprocess; fix
+    // them up before they count against max_concurrent
+    if let Err(e) = state.agent_manager.recover_orphaned_sessions().await {
+        tracing::error!(error = %e, "Failed to recover orphaned agent sessions");
+    }
+
+    // Start anything left in the queue by a previous run
+    if let Err(e) = state.agent_manager.dispatch_queue().await {
+        tracing::error!(error = %e, "Failed to dispatch queued agent sessions");
+    }
+
+    // Scheduled sessions
+    scheduler::sync_config_schedules(&database, &config.agents.schedules).await?;
+    let scheduler_task = tokio::spawn(scheduler::run(state.clone()));
+    let retention_task = tokio::spawn(retention::run(state.clone()));
+
+    let agent_manager = state.agent_manager.clone();
+    let shutdown = state.shutdown.clone();
+
     // Build router
     let app = axum::Router::new()
         .merge(api::router())
@@ -155,7 +233,62 @@ pub async fn run_server(config: PorterConfig) -> anyhow::Result<()> {
         addr
     );
 
-    axum::serve(listener, app).await?;
+    let signal_token = shutdown.clone();
+    let signal_manager = agent_manager.clone();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            tracing::info!("Shutdown requested, draining connections");
+            signal_token.cancel();
+            // Permission prompts are long-polls; answer them so they don't
+            // keep connections open
+            signal_manager.deny_permission_requests();
+        })
+        .await?;
+
+    // Stop background work, then give agent sessions their grace period
+    shutdown.cancel();
+    for task in tick_tasks {
+        let _ = task.await;
+    }
+    let _ = scheduler_task.await;
+    let _ = retention_task.await;
+    agent_manager
+        .shutdown(Duration::from_secs(config.agents.shutdown_grace_secs))
+        .await;
+
+    database.pool().close().await;
+    tracing::info!("Porter server stopped");
 
     Ok(())
 }
+
+/// Resolve on Ctrl-C, or SIGTERM on Unix.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "Failed to listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
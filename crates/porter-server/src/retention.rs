@@ -0,0 +1,25 @@
+//! Deletes agent sessions `[agents.retention]` no longer keeps.
+//!
+//! Sweeps run at startup and then hourly; `POST /api/agents/prune` runs one
+//! on demand.
+
+use crate::AppState;
+use std::time::Duration;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Sweep until shutdown.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        if let Err(e) = state.agent_manager.prune_sessions(false).await {
+            tracing::error!(error = %e, "Failed to prune agent sessions");
+        }
+    }
+    tracing::debug!("Retention sweeper stopped");
+}
@@ -0,0 +1,134 @@
+//! Starts agent sessions for due schedules.
+//!
+//! Schedules are polled rather than timed individually, so edits through the
+//! API take effect on the next poll. A run missed while the server was down
+//! fires once at startup; the next run is then computed from the current time.
+
+use crate::AppState;
+use chrono::Utc;
+use porter_core::agents::SessionOptions;
+use porter_core::config::ScheduleConfig;
+use porter_core::db::Database;
+use porter_core::models::{CreateSchedule, Schedule, UpdateSchedule, WsEvent};
+use porter_core::schedules::Cron;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Make the config-defined schedules in the database match `[[agents.schedules]]`:
+/// create or update them by name, and delete ones no longer in the config.
+pub async fn sync_config_schedules(db: &Database, configs: &[ScheduleConfig]) -> anyhow::Result<()> {
+    for config in configs {
+        match db.get_schedule_by_name(&config.name).await? {
+            Some(existing) if !existing.from_config => {
+                tracing::warn!(
+                    schedule = %config.name,
+                    "A schedule with this name was created through the API; ignoring config entry"
+                );
+            }
+            Some(existing) => {
+                let update = UpdateSchedule {
+                    name: None,
+                    cron: Some(config.cron.clone()),
+                    prompt: Some(config.prompt.clone()),
+                    directory: config.directory.clone(),
+                    dangerously_skip_permissions: Some(config.dangerously_skip_permissions),
+                    enabled: Some(config.enabled),
+                };
+                db.update_schedule(&existing.id, update)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Schedule '{}': {e}", config.name))?;
+            }
+            None => {
+                let input = CreateSchedule {
+                    name: config.name.clone(),
+                    cron: config.cron.clone(),
+                    prompt: config.prompt.clone(),
+                    directory: config.directory.clone(),
+                    dangerously_skip_permissions: config.dangerously_skip_permissions,
+                    enabled: Some(config.enabled),
+                };
+                db.create_schedule(input, true)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Schedule '{}': {e}", config.name))?;
+                tracing::info!(schedule = %config.name, "Added schedule from config");
+            }
+        }
+    }
+
+    for schedule in db.list_schedules().await? {
+        if schedule.from_config && !configs.iter().any(|c| c.name == schedule.name) {
+            db.delete_schedule(&schedule.id).await?;
+            tracing::info!(schedule = %schedule.name, "Removed schedule no longer in config");
+        }
+    }
+
+    Ok(())
+}
+
+/// Poll for due schedules until shutdown.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = state.shutdown.cancelled() => break,
+            _ = interval.tick() => {}
+        }
+
+        let due = match state.db.list_due_schedules(Utc::now()).await {
+            Ok(due) => due,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to load due schedules");
+                continue;
+            }
+        };
+        for schedule in due {
+            if let Err(e) = fire(&state, &schedule).await {
+                tracing::error!(schedule = %schedule.name, error = %e, "Failed to record schedule run");
+            }
+        }
+    }
+    tracing::debug!("Scheduler stopped");
+}
+
+/// Start a session for `schedule` and advance it to its next run. A session
+/// that can't be started (e.g. over budget) is reported as a notification;
+/// the schedule still advances so it doesn't retry every poll.
+pub async fn fire(state: &AppState, schedule: &Schedule) -> anyhow::Result<Option<String>> {
+    let now = Utc::now();
+    let opts = SessionOptions {
+        working_directory: schedule.working_directory.clone(),
+        dangerously_skip_permissions: schedule.dangerously_skip_permissions,
+        schedule_id: Some(schedule.id.clone()),
+        ..Default::default()
+    };
+
+    let session_id = match state.agent_manager.start_session(&schedule.prompt, opts).await {
+        Ok(session) => {
+            tracing::info!(schedule = %schedule.name, session_id = %session.id, "Started scheduled session");
+            Some(session.id)
+        }
+        Err(e) => {
+            tracing::warn!(schedule = %schedule.name, error = %e, "Scheduled session could not start");
+            let message = format!("Schedule '{}' could not start a session: {e}", schedule.name);
+            let notification = state
+                .db
+                .create_notification("schedule_failed", &message, None)
+                .await?;
+            let _ = state.ws_tx.send(WsEvent::Notification(notification));
+            None
+        }
+    };
+
+    let next_run_at = if schedule.enabled {
+        schedule.cron.parse::<Cron>()?.next_after(now)
+    } else {
+        None
+    };
+    state
+        .db
+        .record_schedule_run(&schedule.id, session_id.as_deref(), now, next_run_at)
+        .await?;
+
+    Ok(session_id)
+}
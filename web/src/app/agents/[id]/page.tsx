@@ -310,6 +310,47 @@ function MessageBubble({ message }: { message: AgentMessage }) {
   const isUser = message.role === "user";
   const isError = message.role === "error";
 
+  if (message.content_type === "init") return null;
+
+  if (message.content_type === "thinking") {
+    return (
+      <details className="ml-10 rounded-lg bg-muted/30 border border-border/50">
+        <summary className="px-3 py-1.5 text-xs text-muted-foreground cursor-pointer select-none">
+          Thinking
+        </summary>
+        <div className="px-3 pb-2 text-sm italic text-muted-foreground/70 whitespace-pre-wrap">
+          {message.content}
+        </div>
+      </details>
+    );
+  }
+
+  if (
+    message.content_type === "tool_use" ||
+    message.content_type === "tool_result"
+  ) {
+    const isResult = message.content_type === "tool_result";
+    const body = isResult
+      ? message.content
+      : JSON.stringify(message.tool_input, null, 2);
+    return (
+      <details
+        className={cn(
+          "ml-10 rounded-md border text-xs font-mono",
+          message.is_error
+            ? "border-destructive/30 bg-destructive/5 text-destructive"
+            : "border-border/50 bg-muted/50 text-muted-foreground"
+        )}
+      >
+        <summary className="inline-flex items-center gap-1.5 px-2.5 py-1 cursor-pointer select-none">
+          <Wrench className="h-3 w-3" />
+          {isResult ? `${message.tool_name ?? "tool"} result` : message.tool_name}
+        </summary>
+        <pre className="px-3 pb-2 whitespace-pre-wrap break-all">{body}</pre>
+      </details>
+    );
+  }
+
   return (
     <div className="flex gap-3">
       <div
@@ -49,20 +49,57 @@ export interface UpdateTask {
 export interface AgentSession {
   id: string;
   prompt: string;
-  status: "running" | "paused" | "completed" | "failed";
+  status: "queued" | "running" | "paused" | "completed" | "failed";
   model: string;
   claude_session_id: string | null;
   working_directory: string | null;
   dangerously_skip_permissions: boolean;
+  usage: TokenUsage;
+  queue_position: number | null;
+  append_system_prompt: string | null;
+  allowed_tools: string[];
+  disallowed_tools: string[];
+  max_turns: number | null;
+  mcp_servers: string[] | null;
+  profile: string | null;
+  permission_mode: "default" | "acceptEdits" | "plan" | "bypassPermissions" | null;
+  timeout_secs: number | null;
+  startup_timeout_secs: number | null;
+  timeout_mode: "total" | "idle" | null;
+  parent_session_id: string | null;
+  worktree_repo: string | null;
+  worktree_branch: string | null;
+  starred: boolean;
+  backend: string | null;
   started_at: string;
   completed_at: string | null;
 }
 
+export interface SessionFile {
+  path: string;
+  size: number;
+  modified: string;
+}
+
+export interface TokenUsage {
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+  cost_usd: number;
+  duration_ms: number;
+}
+
 export interface AgentMessage {
   id: string;
   session_id: string;
   role: string;
   content: string;
+  content_type: "text" | "thinking" | "tool_use" | "tool_result" | "init";
+  tool_name: string | null;
+  tool_input: unknown;
+  tool_use_id: string | null;
+  is_error: boolean;
   timestamp: string;
 }
 